@@ -0,0 +1,168 @@
+//! Who is who across exports: the exporter shown as "You", and the several
+//! names one person can appear under, mapped together by an aliases file.
+
+use crate::error::Error;
+use crate::message::{Message, MessageKind};
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+/// How exports write the person who exported the chat
+pub const YOU: &str = "You";
+
+/// Rewrite a message written or caused by "You" as coming from `me`
+pub fn resolve_you(m: &mut Message, me: &str) {
+    if m.author != YOU {
+        return;
+    }
+    // System events start with their actor: `You added Bob`
+    if let MessageKind::System(_) = m.kind {
+        if let Some(rest) = m.text.strip_prefix(YOU) {
+            m.text = format!("{}{}", me, rest);
+        }
+    }
+    m.author = me.to_string();
+}
+
+/// Works out who exported a chat. System events the exporter caused are
+/// written as "You added Bob" while their messages may carry their name, so the
+/// exporter is the one author never named in a system event, if there is one.
+/// Only the authors and the words of system events are kept, not the events.
+#[derive(Debug, Default)]
+pub struct ExporterGuess {
+    you_acted: bool, // a system event was caused by "You"
+    unnamed: HashSet<String>, // authors not named in any system event so far
+    named_words: HashSet<String>, // the words of every system event
+}
+
+fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.split(|c: char| c.is_whitespace() || c == ',').filter(|w| !w.is_empty())
+}
+
+impl ExporterGuess {
+    /// Take a message into account
+    pub fn add(&mut self, m: &Message) {
+        match m.kind {
+            MessageKind::System(_) => {
+                self.you_acted |= m.author == YOU;
+                self.named_words.extend(words(&m.text).map(str::to_string));
+                let named_words = &self.named_words;
+                self.unnamed.retain(|a| !words(a).all(|w| named_words.contains(w)));
+            }
+            _ if m.author == YOU || self.unnamed.contains(&m.author) => {}
+            _ if words(&m.author).all(|w| self.named_words.contains(w)) => {}
+            _ => {
+                self.unnamed.insert(m.author.clone());
+            }
+        }
+    }
+
+    /// The exporter's name, when the chat gives it away
+    pub fn guess(&self) -> Option<&str> {
+        if !self.you_acted {
+            return None;
+        }
+        let mut unnamed = self.unnamed.iter();
+        match (unnamed.next(), unnamed.next()) {
+            (Some(me), None) => Some(me),
+            _ => None,
+        }
+    }
+}
+
+/// The ways one person is written in exports, from a table in the aliases file:
+///
+/// ```toml
+/// [Sam]
+/// names = ["+1 555 123 4567", "~ Sam"]
+/// patterns = ["(?i)^sammy?$"]
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    #[serde(default)]
+    names: Vec<String>, // authors written exactly like this
+    #[serde(default)]
+    patterns: Vec<String>, // regexes matching the whole or part of an author
+}
+
+/// Maps raw author names to the canonical name of the person behind them
+#[derive(Debug, Default)]
+pub struct Aliases {
+    names: HashMap<String, String>,
+    patterns: Vec<(Regex, String)>, // tried in file order after the exact names
+    resolved: HashMap<String, Option<String>>, // pattern lookups already done
+}
+
+impl Aliases {
+    /// Read an aliases file
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let error = |e: &dyn std::fmt::Display| Error::Aliases(format!("{}: {}", path, e.to_string().trim_end()));
+        let text = std::fs::read_to_string(path).map_err(|e| error(&e))?;
+        // Keep the file order so that the first matching pattern wins
+        let table: toml::Table = toml::from_str(&text).map_err(|e| error(&e))?;
+
+        let mut aliases = Aliases::default();
+        for (canonical, entry) in table {
+            let entry: Entry = entry.try_into().map_err(|e| error(&format!("[{}]: {}", canonical, e)))?;
+            for name in entry.names {
+                if let Some(other) = aliases.names.insert(name.clone(), canonical.clone()) {
+                    if other != canonical {
+                        return Err(error(&format!("\"{}\" is listed under both [{}] and [{}]", name, other, canonical)));
+                    }
+                }
+            }
+            for pattern in entry.patterns {
+                let re = Regex::new(&pattern).map_err(|e| error(&format!("[{}]: {}", canonical, e)))?;
+                aliases.patterns.push((re, canonical.clone()));
+            }
+        }
+        Ok(aliases)
+    }
+
+    /// The canonical name for `author`, or None if the file doesn't mention it
+    pub fn resolve(&mut self, author: &str) -> Option<&str> {
+        if let Some(canonical) = self.names.get(author) {
+            return Some(canonical);
+        }
+        if !self.resolved.contains_key(author) {
+            let canonical = self.patterns.iter().find(|(re, _)| re.is_match(author)).map(|(_, c)| c.clone());
+            self.resolved.insert(author.to_string(), canonical);
+        }
+        self.resolved[author].as_deref()
+    }
+
+    /// Rename the author of a message to their canonical name. The actors of
+    /// system events are left as written, as they are part of the event's text.
+    pub fn apply(&mut self, m: &mut Message) {
+        if let MessageKind::System(_) = m.kind {
+            return;
+        }
+        if let Some(canonical) = self.resolve(&m.author) {
+            m.author = canonical.to_string();
+        }
+    }
+}
+
+/// How many messages each raw author name wrote, for building an aliases file
+#[derive(Debug, Default)]
+pub struct AuthorCounts {
+    counts: HashMap<String, u64>,
+}
+
+impl AuthorCounts {
+    /// Count a message for its author, unless it is a system event
+    pub fn add(&mut self, m: &Message) {
+        if let MessageKind::System(_) = m.kind {
+            return;
+        }
+        *self.counts.entry(m.author.clone()).or_default() += 1;
+    }
+
+    /// The raw names with their counts, most messages first
+    pub fn into_sorted(self) -> Vec<(String, u64)> {
+        let mut counts: Vec<(String, u64)> = self.counts.into_iter().collect();
+        counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+        counts
+    }
+}
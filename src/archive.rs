@@ -0,0 +1,66 @@
+use crate::error::Error;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+/// A zip made with "Export chat", holding the chat text and the attached media
+pub(crate) struct ChatArchive {
+    archive: ZipArchive<File>,
+    chat_index: usize, // index of the chat text in the zip
+    pub media_sizes: HashMap<String, u64>, // size in bytes of every other file, by file name
+}
+
+pub(crate) fn is_zip(path: &str) -> bool {
+    path.to_lowercase().ends_with(".zip")
+}
+
+fn chat_rank(name: &str) -> Option<u8> {
+    // iOS calls the chat `_chat.txt`, Android `WhatsApp Chat with Name.txt`
+    if name == "_chat.txt" {
+        Some(0)
+    } else if name.starts_with("WhatsApp Chat") && name.ends_with(".txt") {
+        Some(1)
+    } else if name.ends_with(".txt") {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+impl ChatArchive {
+    pub fn open(file: File) -> Result<Self, Error> {
+        let mut archive = ZipArchive::new(file)?;
+
+        let mut files = Vec::new();
+        for i in 0..archive.len() {
+            let file = archive.by_index(i)?;
+            if file.is_dir() {
+                continue;
+            }
+            let name = file.name()?;
+            let name = name.rsplit('/').next().unwrap_or_default().to_string();
+            files.push((i, name, file.size()));
+        }
+
+        let (chat_index, _) = files
+            .iter()
+            .filter_map(|(i, name, _)| chat_rank(name).map(|rank| (*i, rank)))
+            .min_by_key(|&(i, rank)| (rank, i))
+            .ok_or(ZipError::FileNotFound)?;
+
+        let media_sizes = files
+            .into_iter()
+            .filter(|(i, _, _)| *i != chat_index)
+            .map(|(_, name, size)| (name, size))
+            .collect();
+
+        Ok(ChatArchive { archive, chat_index, media_sizes })
+    }
+
+    /// A reader over the chat text, decompressed as it is read
+    pub fn chat(&mut self) -> Result<impl Read + '_, Error> {
+        Ok(self.archive.by_index(self.chat_index)?)
+    }
+}
@@ -0,0 +1,130 @@
+use std::fmt;
+use std::io;
+use zip::result::ZipError;
+
+/// Everything that can go wrong reading chats
+#[derive(Debug)]
+pub enum Error {
+    /// A file or stdin couldn't be read or written
+    Io(io::Error),
+    /// An exported zip is broken or has no chat in it
+    Zip(ZipError),
+    /// A line couldn't be parsed in strict mode
+    Parse(ParseError),
+    /// The aliases file can't be read or is invalid
+    Aliases(String),
+    /// An export from another messenger can't be read
+    Import(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Zip(ZipError::FileNotFound) => write!(f, "no chat text file in the zip"),
+            Error::Zip(e) => write!(f, "{}", e),
+            Error::Parse(e) => write!(f, "{}", e),
+            Error::Aliases(e) => write!(f, "invalid aliases file {}", e),
+            Error::Import(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ZipError> for Error {
+    fn from(e: ZipError) -> Self {
+        Error::Zip(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+/// Why a line couldn't be parsed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// The line isn't valid UTF-8
+    InvalidUtf8,
+    /// The line looks like a message but its date or time doesn't exist
+    InvalidTimestamp,
+    /// The line is text before the first message of the file
+    NoMessage,
+}
+
+/// A line that couldn't be parsed, with where it is
+#[derive(Debug)]
+pub struct ParseError {
+    /// The input the line is in
+    pub source: String,
+    /// The 1-based line number in the input
+    pub line: usize,
+    /// The offending line
+    pub text: String,
+    /// What is wrong with it
+    pub kind: ParseErrorKind,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.kind {
+            ParseErrorKind::InvalidUtf8 => "invalid UTF-8",
+            ParseErrorKind::InvalidTimestamp => "invalid timestamp",
+            ParseErrorKind::NoMessage => "text before the first message",
+        };
+        write!(f, "{}:{}: {}", self.source, self.line, reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Lines that couldn't be read cleanly in lenient mode
+#[derive(Debug, Default)]
+pub struct ParseReport {
+    /// Lines decoded with replacement characters
+    pub invalid_utf8: usize,
+    /// Lines with an impossible timestamp, appended to the previous message
+    pub invalid_timestamps: usize,
+    /// Lines before the first message, skipped
+    pub skipped: usize,
+    /// Records of a structured export that couldn't be read, skipped
+    pub skipped_records: usize,
+}
+
+impl ParseReport {
+    /// Whether everything was read cleanly
+    pub fn is_empty(&self) -> bool {
+        self.invalid_utf8 == 0 && self.invalid_timestamps == 0 && self.skipped == 0 && self.skipped_records == 0
+    }
+}
+
+impl fmt::Display for ParseReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if self.invalid_utf8 > 0 {
+            parts.push(format!("{} with invalid UTF-8 decoded lossily", self.invalid_utf8));
+        }
+        if self.invalid_timestamps > 0 {
+            parts.push(format!(
+                "{} with an invalid timestamp appended to the previous message",
+                self.invalid_timestamps
+            ));
+        }
+        if self.skipped > 0 {
+            parts.push(format!("{} before the first message skipped", self.skipped));
+        }
+        if self.skipped_records > 0 {
+            parts.push(format!("{} records that couldn't be read skipped", self.skipped_records));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
@@ -0,0 +1,188 @@
+use crate::message::Message;
+use crate::parse;
+use chrono::{DateTime, FixedOffset, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Utc};
+use chrono_tz::Tz;
+use std::str::FromStr;
+
+/// A unit of time for dates relative to now
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeUnit {
+    /// Hours, written `h`
+    Hours,
+    /// Days, written `d`
+    Days,
+    /// Weeks, written `w`
+    Weeks,
+    /// Calendar months, written `m`
+    Months,
+    /// Calendar years, written `y`
+    Years,
+}
+
+/// A date given on the command line: a calendar date, a date and time, or an
+/// amount of time before now like `90d`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateBound {
+    /// A calendar date: `2024-01-31`
+    Date(NaiveDate),
+    /// A date and time: `2024-01-31 18:00`
+    DateTime(NaiveDateTime),
+    /// An amount of time before now: `90d`
+    Ago(u32, TimeUnit),
+}
+
+impl FromStr for DateBound {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(DateBound::Date(date));
+        }
+        for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"] {
+            if let Ok(time) = NaiveDateTime::parse_from_str(s, format) {
+                return Ok(DateBound::DateTime(time));
+            }
+        }
+        // e.g. `36h`, `90d`, `2w`, `6m`, `1y`
+        let unit = match s.chars().last() {
+            Some('h') => TimeUnit::Hours,
+            Some('d') => TimeUnit::Days,
+            Some('w') => TimeUnit::Weeks,
+            Some('m') => TimeUnit::Months,
+            Some('y') => TimeUnit::Years,
+            _ => return Err(format!("expected a date like 2024-01-31 or a time before now like 90d, not \"{}\"", s)),
+        };
+        let amount = s[..s.len() - 1].parse().map_err(|_| format!("expected an amount before the unit in \"{}\"", s))?;
+        Ok(DateBound::Ago(amount, unit))
+    }
+}
+
+impl DateBound {
+    /// The moment this date stands for, with dates and times read in `timezone`.
+    /// A calendar date is its midnight, or the midnight after it for the `end` of a range.
+    pub fn resolve(self, timezone: Tz, now: DateTime<Utc>, end: bool) -> Option<DateTime<FixedOffset>> {
+        match self {
+            DateBound::Date(date) => {
+                let date = if end { date.succ_opt()? } else { date };
+                Some(parse::localize(date.and_time(NaiveTime::MIN), timezone, None))
+            }
+            DateBound::DateTime(time) => Some(parse::localize(time, timezone, None)),
+            DateBound::Ago(amount, unit) => {
+                let then = match unit {
+                    TimeUnit::Hours => now.checked_sub_signed(TimeDelta::try_hours(amount.into())?)?,
+                    TimeUnit::Days => now.checked_sub_signed(TimeDelta::try_days(amount.into())?)?,
+                    TimeUnit::Weeks => now.checked_sub_signed(TimeDelta::try_weeks(amount.into())?)?,
+                    TimeUnit::Months => now.checked_sub_months(Months::new(amount))?,
+                    TimeUnit::Years => now.checked_sub_months(Months::new(amount.checked_mul(12)?))?,
+                };
+                Some(then.fixed_offset())
+            }
+        }
+    }
+}
+
+/// Which messages to compute stats for. An empty filter lets every message through.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Leave out messages before this moment
+    pub since: Option<DateTime<FixedOffset>>,
+    /// Leave out messages from this moment on
+    pub until: Option<DateTime<FixedOffset>>,
+    /// Only keep messages by these authors, unless empty. Names are compared ignoring case.
+    pub users: Vec<String>,
+    /// Leave out messages by these authors
+    pub exclude_users: Vec<String>,
+}
+
+impl Filter {
+    /// Whether a message sent at `timestamp` is kept
+    pub fn in_range(&self, timestamp: DateTime<FixedOffset>) -> bool {
+        self.since.is_none_or(|since| timestamp >= since) && self.until.is_none_or(|until| timestamp < until)
+    }
+
+    /// Whether messages by `author` are kept
+    pub fn keeps_author(&self, author: &str) -> bool {
+        let named = |names: &[String]| {
+            let author = author.to_lowercase();
+            names.iter().any(|n| n.to_lowercase() == author)
+        };
+        (self.users.is_empty() || named(&self.users)) && (self.exclude_users.is_empty() || !named(&self.exclude_users))
+    }
+
+    /// Whether `m` is kept
+    pub fn matches(&self, m: &Message) -> bool {
+        self.in_range(m.timestamp) && self.keeps_author(&m.author)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-05-31T12:00:00Z").unwrap().to_utc()
+    }
+
+    fn resolve(bound: &str, end: bool) -> String {
+        bound.parse::<DateBound>().unwrap().resolve(chrono_tz::Europe::Berlin, now(), end).unwrap().to_rfc3339()
+    }
+
+    #[test]
+    fn dates_times_and_amounts_ago_are_read() {
+        assert_eq!("2024-01-31".parse(), Ok(DateBound::Date(NaiveDate::from_ymd_opt(2024, 1, 31).unwrap())));
+        let evening = NaiveDate::from_ymd_opt(2024, 1, 31).unwrap().and_hms_opt(18, 0, 0).unwrap();
+        assert_eq!("2024-01-31 18:00".parse(), Ok(DateBound::DateTime(evening)));
+        assert_eq!("2024-01-31T18:00:00".parse(), Ok(DateBound::DateTime(evening)));
+        assert_eq!(" 90d ".parse(), Ok(DateBound::Ago(90, TimeUnit::Days)));
+        assert_eq!("6m".parse(), Ok(DateBound::Ago(6, TimeUnit::Months)));
+        assert!("yesterday".parse::<DateBound>().is_err());
+        assert!("d".parse::<DateBound>().is_err());
+        assert!("-3d".parse::<DateBound>().is_err());
+    }
+
+    #[test]
+    fn amounts_ago_count_back_from_now() {
+        assert_eq!(resolve("90d", false), "2024-03-02T12:00:00+00:00");
+        assert_eq!(resolve("36h", false), "2024-05-30T00:00:00+00:00");
+        // Calendar months, clamped to the end of a shorter month
+        assert_eq!(resolve("6m", false), "2023-11-30T12:00:00+00:00");
+        assert_eq!(resolve("1y", false), "2023-05-31T12:00:00+00:00");
+        assert_eq!(DateBound::Ago(u32::MAX, TimeUnit::Years).resolve(chrono_tz::UTC, now(), false), None);
+    }
+
+    #[test]
+    fn dates_are_read_in_the_timezone() {
+        assert_eq!(resolve("2024-01-31", false), "2024-01-31T00:00:00+01:00");
+        assert_eq!(resolve("2024-07-01 18:00", false), "2024-07-01T18:00:00+02:00");
+    }
+
+    #[test]
+    fn the_until_date_is_kept_whole() {
+        let filter = Filter {
+            since: "2024-01-30".parse::<DateBound>().unwrap().resolve(chrono_tz::Europe::Berlin, now(), false),
+            until: "2024-01-31".parse::<DateBound>().unwrap().resolve(chrono_tz::Europe::Berlin, now(), true),
+            ..Filter::default()
+        };
+        let at = |time: &str| DateTime::parse_from_rfc3339(time).unwrap();
+        assert!(!filter.in_range(at("2024-01-29T23:59:59+01:00")));
+        assert!(filter.in_range(at("2024-01-30T00:00:00+01:00")));
+        assert!(filter.in_range(at("2024-01-31T23:59:59+01:00")));
+        assert!(!filter.in_range(at("2024-02-01T00:00:00+01:00")));
+        // The same moment written in another timezone
+        assert!(filter.in_range(at("2024-01-31T22:30:00+00:00")));
+        assert!(!filter.in_range(at("2024-01-31T23:00:00+00:00")));
+    }
+
+    #[test]
+    fn authors_are_compared_ignoring_case() {
+        let filter = Filter { users: vec!["alice".to_string(), "BOB".to_string()], exclude_users: vec!["Bob".to_string()], ..Filter::default() };
+        assert!(filter.keeps_author("Alice"));
+        assert!(filter.keeps_author("ALICE"));
+        assert!(!filter.keeps_author("bob"));
+        assert!(!filter.keeps_author("Carol"));
+        assert!(Filter::default().keeps_author("Carol"));
+        let exclude = Filter { exclude_users: vec!["ÉMILE".to_string()], ..Filter::default() };
+        assert!(!exclude.keeps_author("émile"));
+    }
+}
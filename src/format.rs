@@ -0,0 +1,657 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use regex::{Captures, Regex};
+use std::fmt;
+
+/// How many message headers to look at when sniffing the export format
+const SNIFF_LINES: usize = 200;
+
+/// How a message line is laid out, which differs between phones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// iOS: `[3/1/17, 11:36:03 PM] Name: text`
+    Ios,
+    /// Android: `3/1/17, 23:36 - Name: text`
+    Android,
+}
+
+/// Whether dates put the month or the day first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateOrder {
+    /// `3/1/17` is March 1st
+    MonthFirst,
+    /// `3/1/17` is January 3rd
+    DayFirst,
+}
+
+impl fmt::Display for DateOrder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DateOrder::MonthFirst => write!(f, "month-first"),
+            DateOrder::DayFirst => write!(f, "day-first"),
+        }
+    }
+}
+
+/// How times are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Clock {
+    /// A 12-hour clock with AM/PM
+    H12,
+    /// A 24-hour clock
+    H24,
+}
+
+/// The details of how dates and times are written, so that a chat can be written back the same way
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Style {
+    /// The character between the parts of the date
+    pub separator: char,
+    /// Whether days and months have a leading zero
+    pub padded: bool,
+    /// Whether the year comes first: `2017-03-01`
+    pub year_first: bool,
+    /// Whether years have four digits
+    pub long_year: bool,
+    /// Whether times have seconds
+    pub seconds: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style { separator: '/', padded: false, year_first: false, long_year: false, seconds: false }
+    }
+}
+
+impl Style {
+    fn of(header: &Header) -> Self {
+        let [a, b, c] = date_parts(header.date).unwrap_or(["", "", ""]);
+        Style {
+            separator: header.date.chars().find(|c| !c.is_ascii_digit()).unwrap_or('/'),
+            padded: a.starts_with('0') || b.starts_with('0'),
+            year_first: a.len() == 4,
+            long_year: a.len() == 4 || c.len() == 4,
+            seconds: header.time.matches([':', '.']).count() == 2,
+        }
+    }
+}
+
+/// The format of a WhatsApp export, which depends on the phone and its locale
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Format {
+    /// How lines are laid out
+    pub layout: Layout,
+    /// Whether dates are month-first or day-first
+    pub date_order: DateOrder,
+    /// Whether times use a 12-hour or a 24-hour clock
+    pub clock: Clock,
+    /// The details of how dates and times are written
+    pub style: Style,
+}
+
+impl Default for Format {
+    /// An Android export with day-first dates and a 24-hour clock: `01/03/2017, 23:36 - `
+    fn default() -> Self {
+        Format {
+            layout: Layout::Android,
+            date_order: DateOrder::DayFirst,
+            clock: Clock::H24,
+            style: Style { padded: true, long_year: true, ..Style::default() },
+        }
+    }
+}
+
+/// The WhatsApp export format to read a chat in, or `Auto` to detect it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum FormatArg {
+    /// Sniff the format from the start of the file
+    Auto,
+    /// iOS export, month-first dates
+    IosUs,
+    /// iOS export, day-first dates
+    IosEu,
+    /// Android export, month-first dates
+    AndroidUs,
+    /// Android export, day-first dates
+    AndroidEu,
+}
+
+impl FormatArg {
+    /// The layout and date order this stands for, or None to detect them
+    pub fn preset(self) -> Option<(Layout, DateOrder)> {
+        match self {
+            FormatArg::Auto => None,
+            FormatArg::IosUs => Some((Layout::Ios, DateOrder::MonthFirst)),
+            FormatArg::IosEu => Some((Layout::Ios, DateOrder::DayFirst)),
+            FormatArg::AndroidUs => Some((Layout::Android, DateOrder::MonthFirst)),
+            FormatArg::AndroidEu => Some((Layout::Android, DateOrder::DayFirst)),
+        }
+    }
+}
+
+/// The format a file will be parsed with and how sure we are about its date order
+#[derive(Debug, Clone, Copy)]
+pub struct Detection {
+    /// The format the file will be parsed with
+    pub format: Format,
+    /// How sure the date order is, between 0.5 (a coin toss) and 1.0
+    pub date_order_confidence: f32,
+    /// Whether the format was given rather than detected
+    pub pinned: bool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DateOrderGuess {
+    pub order: DateOrder,
+    pub confidence: f32,
+}
+
+/// The pieces of a line that starts a new message
+#[derive(Debug)]
+pub(crate) struct Header<'a> {
+    pub date: &'a str,
+    pub time: &'a str,
+    pub meridiem: Option<&'a str>,
+    pub rest: &'a str, // `Name: text`, or just the text of a system message
+}
+
+/// Localized AM/PM markers as (am, pm), compared case-insensitively
+const MERIDIEMS: &[(&str, &str)] = &[
+    ("AM", "PM"),
+    ("a.m.", "p.m."),
+    ("a. m.", "p. m."),
+    ("vorm.", "nachm."),
+    ("午前", "午後"),
+    ("上午", "下午"),
+    ("오전", "오후"),
+    ("π.μ.", "μ.μ."),
+    ("ÖÖ", "ÖS"),
+    ("ص", "م"),
+];
+
+/// The spaces WhatsApp puts between a time and its meridiem
+const SPACES: &str = r"[ \u{202F}\u{00A0}]";
+
+/// Whether a meridiem marker means the afternoon, or None if it isn't one we know
+fn is_pm(meridiem: &str) -> Option<bool> {
+    let normalize = |m: &str| m.replace(['\u{202F}', '\u{00A0}'], " ").to_lowercase();
+    let meridiem = normalize(meridiem);
+    MERIDIEMS.iter().find_map(|(am, pm)| {
+        if normalize(am) == meridiem {
+            Some(false)
+        } else if normalize(pm) == meridiem {
+            Some(true)
+        } else {
+            None
+        }
+    })
+}
+
+fn meridiem_pattern() -> String {
+    let mut markers: Vec<&str> = MERIDIEMS.iter().flat_map(|(am, pm)| [*am, *pm]).collect();
+    // Longest first so `a. m.` isn't cut short by a shorter marker
+    markers.sort_by_key(|m| std::cmp::Reverse(m.len()));
+    let markers: Vec<String> = markers
+        .iter()
+        .map(|m| regex::escape(m).replace(' ', SPACES))
+        .collect();
+    format!("(?i:{})", markers.join("|"))
+}
+
+pub(crate) struct LineMatcher {
+    ios: Regex,
+    android: Regex,
+}
+
+impl Default for LineMatcher {
+    fn default() -> Self {
+        LineMatcher::new()
+    }
+}
+
+impl LineMatcher {
+    pub fn new() -> Self {
+        let date = r"(?P<date>\d{1,4}[./-]\d{1,2}[./-]\d{1,4})";
+        let meridiem = meridiem_pattern();
+        // Most locales write the meridiem after the time, some (Korean, Japanese) before it
+        let time = format!(
+            r"(?:(?P<pre>{meridiem}){SPACES}?)?(?P<time>\d{{1,2}}[:.]\d{{2}}(?:[:.]\d{{2}})?)(?:{SPACES}?(?P<post>{meridiem}))?"
+        );
+        let rest = r"(?P<rest>.*)";
+        LineMatcher {
+            // iOS prefixes lines of system messages and attachments with a left-to-right mark
+            ios: Regex::new(&format!(r"^\u{{200E}}?\[{date},? {time}\] {rest}")).unwrap(),
+            android: Regex::new(&format!(r"^{date},? {time} - {rest}")).unwrap(),
+        }
+    }
+
+    pub fn header<'a>(&self, layout: Layout, line: &'a str) -> Option<Header<'a>> {
+        let re = match layout {
+            Layout::Ios => &self.ios,
+            Layout::Android => &self.android,
+        };
+        re.captures(line).map(|caps| header_from(&caps))
+    }
+}
+
+fn header_from<'a>(caps: &Captures<'a>) -> Header<'a> {
+    Header {
+        date: caps.name("date").unwrap().as_str(),
+        time: caps.name("time").unwrap().as_str(),
+        meridiem: caps.name("pre").or(caps.name("post")).map(|m| m.as_str()),
+        rest: caps.name("rest").unwrap().as_str(),
+    }
+}
+
+impl Format {
+    /// Turn the date and time of a header into a timestamp, or None if they are
+    /// not valid in this format
+    pub(crate) fn timestamp(&self, header: &Header) -> Option<NaiveDateTime> {
+        let date = parse_date(header.date, self.date_order)?;
+        let time = parse_time(header.time, header.meridiem, self.clock)?;
+        Some(date.and_time(time))
+    }
+
+    /// Write the start of a message line for `timestamp`, up to where the author begins
+    pub fn render_header(&self, timestamp: NaiveDateTime) -> String {
+        let style = self.style;
+        let sep = style.separator;
+        let year = if style.long_year { format!("{:04}", timestamp.year()) } else { format!("{:02}", timestamp.year() % 100) };
+        let (month, day) = (timestamp.month(), timestamp.day());
+        let (a, b) = match self.date_order {
+            DateOrder::MonthFirst => (month, day),
+            DateOrder::DayFirst => (day, month),
+        };
+        let date = match (style.year_first, style.padded) {
+            (true, _) => format!("{}{sep}{:02}{sep}{:02}", year, month, day),
+            (false, true) => format!("{:02}{sep}{:02}{sep}{}", a, b, year),
+            (false, false) => format!("{}{sep}{}{sep}{}", a, b, year),
+        };
+
+        let seconds = if style.seconds { format!(":{:02}", timestamp.second()) } else { String::new() };
+        let time = match self.clock {
+            Clock::H24 => format!("{:02}:{:02}{}", timestamp.hour(), timestamp.minute(), seconds),
+            Clock::H12 => {
+                let (pm, hour) = timestamp.hour12();
+                let meridiem = if pm { "PM" } else { "AM" };
+                format!("{}:{:02}{}\u{202F}{}", hour, timestamp.minute(), seconds, meridiem)
+            }
+        };
+
+        match self.layout {
+            Layout::Ios => format!("[{}, {}] ", date, time),
+            Layout::Android => format!("{}, {} - ", date, time),
+        }
+    }
+}
+
+fn date_parts(date: &str) -> Option<[&str; 3]> {
+    let mut parts = date.split(['/', '.', '-']);
+    let parts = [parts.next()?, parts.next()?, parts.next()?];
+    Some(parts)
+}
+
+pub(crate) fn parse_date(date: &str, order: DateOrder) -> Option<NaiveDate> {
+    let [a, b, c] = date_parts(date)?;
+    // A leading four digit year always means year-month-day
+    let (year, month, day) = if a.len() == 4 {
+        (a, b, c)
+    } else {
+        match order {
+            DateOrder::MonthFirst => (c, a, b),
+            DateOrder::DayFirst => (c, b, a),
+        }
+    };
+    let mut year: i32 = year.parse().ok()?;
+    if year < 100 {
+        year += 2000;
+    }
+    NaiveDate::from_ymd_opt(year, month.parse().ok()?, day.parse().ok()?)
+}
+
+fn parse_time(time: &str, meridiem: Option<&str>, clock: Clock) -> Option<NaiveTime> {
+    let mut parts = time.split([':', '.']);
+    let mut hour: u32 = parts.next()?.parse().ok()?;
+    let minute: u32 = parts.next()?.parse().ok()?;
+    let second: u32 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+    match (clock, meridiem) {
+        (Clock::H12, Some(m)) => {
+            if !(1..=12).contains(&hour) {
+                return None;
+            }
+            let pm = is_pm(m)?;
+            hour = match (hour, pm) {
+                (12, false) => 0,
+                (12, true) => 12,
+                (h, true) => h + 12,
+                (h, false) => h,
+            };
+        }
+        (Clock::H24, None) => {}
+        _ => return None,
+    }
+    NaiveTime::from_hms_opt(hour, minute, second)
+}
+
+/// How well one date order fits every date in a file
+struct Fit {
+    order: DateOrder,
+    valid: bool,       // every date exists in this order
+    backwards: usize,  // number of times the date goes back in time
+    previous: Option<NaiveDate>,
+}
+
+impl Fit {
+    fn new(order: DateOrder) -> Self {
+        Fit { order, valid: true, backwards: 0, previous: None }
+    }
+
+    fn push(&mut self, date: &str) {
+        if !self.valid {
+            return;
+        }
+        match parse_date(date, self.order) {
+            Some(date) => {
+                if self.previous.is_some_and(|previous| date < previous) {
+                    self.backwards += 1;
+                }
+                self.previous = Some(date);
+            }
+            None => self.valid = false,
+        }
+    }
+}
+
+/// Pick the date order under which all dates are valid and the chat runs
+/// forwards in time. Returns None when the dates don't settle it either way.
+fn best_fit(month_first: Fit, day_first: Fit) -> Option<DateOrderGuess> {
+    let (best, other) = match (month_first.valid, day_first.valid) {
+        (false, false) => return None,
+        (true, false) => return Some(DateOrderGuess { order: DateOrder::MonthFirst, confidence: 1.0 }),
+        (false, true) => return Some(DateOrderGuess { order: DateOrder::DayFirst, confidence: 1.0 }),
+        (true, true) if month_first.backwards <= day_first.backwards => (month_first, day_first),
+        (true, true) => (day_first, month_first),
+    };
+    if best.backwards == other.backwards {
+        return None;
+    }
+
+    let confidence = 0.5 + 0.5 * (other.backwards - best.backwards) as f32 / (other.backwards + best.backwards) as f32;
+    Some(DateOrderGuess { order: best.order, confidence })
+}
+
+/// Works out the format of an export from its lines. The layout and clock are
+/// sniffed from the first message headers, the date order is checked against
+/// every timestamp it is given.
+pub(crate) struct Detector {
+    matcher: LineMatcher,
+    ios: usize,
+    android: usize,
+    meridiems: usize,
+    headers: usize,
+    day_first: bool,
+    month_first: bool,
+    dotted: bool,
+    style: Option<Style>, // how the first header was written
+    // (month-first, day-first) fits for each layout, as we don't know it until the end
+    ios_fits: (Fit, Fit),
+    android_fits: (Fit, Fit),
+}
+
+impl Default for Detector {
+    fn default() -> Self {
+        Detector::new()
+    }
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Detector {
+            matcher: LineMatcher::new(),
+            ios: 0,
+            android: 0,
+            meridiems: 0,
+            headers: 0,
+            day_first: false,
+            month_first: false,
+            dotted: false,
+            style: None,
+            ios_fits: (Fit::new(DateOrder::MonthFirst), Fit::new(DateOrder::DayFirst)),
+            android_fits: (Fit::new(DateOrder::MonthFirst), Fit::new(DateOrder::DayFirst)),
+        }
+    }
+
+    pub fn push(&mut self, line: &str) {
+        let ios = self.matcher.header(Layout::Ios, line);
+        let android = self.matcher.header(Layout::Android, line);
+        if let Some(h) = &ios {
+            self.ios_fits.0.push(h.date);
+            self.ios_fits.1.push(h.date);
+        }
+        if let Some(h) = &android {
+            self.android_fits.0.push(h.date);
+            self.android_fits.1.push(h.date);
+        }
+
+        if self.headers >= SNIFF_LINES {
+            return;
+        }
+        let header = match (ios, android) {
+            (Some(h), _) => {
+                self.ios += 1;
+                h
+            }
+            (None, Some(h)) => {
+                self.android += 1;
+                h
+            }
+            (None, None) => return,
+        };
+        self.headers += 1;
+        if header.meridiem.is_some() {
+            self.meridiems += 1;
+        }
+        if let Some([a, b, _]) = date_parts(header.date) {
+            if a.len() < 4 {
+                self.day_first |= a.parse::<u32>().is_ok_and(|n| n > 12);
+                self.month_first |= b.parse::<u32>().is_ok_and(|n| n > 12);
+            }
+        }
+        self.dotted |= header.date.contains('.');
+        // Dates only show they are padded once a day or month is below ten
+        let style = Style::of(&header);
+        self.style.get_or_insert(style).padded |= style.padded;
+    }
+
+    /// The format to parse with. The layout and date order can be pinned on the
+    /// command line, the clock is always read from the file.
+    pub fn finish(self, arg: FormatArg) -> Detection {
+        let clock = if self.meridiems * 2 > self.headers { Clock::H12 } else { Clock::H24 };
+        let style = self.style.unwrap_or_default();
+        if let Some((layout, date_order)) = arg.preset() {
+            let format = Format { layout, date_order, clock, style };
+            return Detection { format, date_order_confidence: 1.0, pinned: true };
+        }
+
+        let layout = if self.android > self.ios { Layout::Android } else { Layout::Ios };
+        let (month_first, day_first) = match layout {
+            Layout::Ios => self.ios_fits,
+            Layout::Android => self.android_fits,
+        };
+        let (date_order, date_order_confidence) = match best_fit(month_first, day_first) {
+            Some(guess) => (guess.order, guess.confidence),
+            None => {
+                let order = match (self.day_first, self.month_first) {
+                    (true, false) => DateOrder::DayFirst,
+                    (false, true) => DateOrder::MonthFirst,
+                    // Nothing conclusive, go by what the locale usually looks like
+                    _ if self.dotted => DateOrder::DayFirst,
+                    _ => DateOrder::MonthFirst,
+                };
+                (order, 0.5)
+            }
+        };
+
+        let format = Format { layout, date_order, clock, style };
+        Detection { format, date_order_confidence, pinned: false }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::MessageKind;
+    use crate::parse::{MessageReader, ParseOptions};
+
+    fn detect(lines: &[&str]) -> Detection {
+        let mut detector = Detector::new();
+        for line in lines {
+            detector.push(line);
+        }
+        detector.finish(FormatArg::Auto)
+    }
+
+    fn fit(order: DateOrder, dates: &[&str]) -> Fit {
+        let mut fit = Fit::new(order);
+        for date in dates {
+            fit.push(date);
+        }
+        fit
+    }
+
+    #[test]
+    fn ambiguous_dates_go_by_which_order_runs_forwards() {
+        // Every day and month is 12 or less, but only day-first keeps the chat in order
+        let detection = detect(&[
+            "01/02/21, 10:00 - Sam: hi",
+            "03/02/21, 10:00 - Bob: hey",
+            "05/02/21, 10:00 - Sam: still here?",
+            "01/03/21, 10:00 - Bob: yes",
+        ]);
+        assert_eq!(detection.format.layout, Layout::Android);
+        assert_eq!(detection.format.date_order, DateOrder::DayFirst);
+        assert_eq!(detection.format.clock, Clock::H24);
+        assert_eq!(detection.date_order_confidence, 1.0);
+        assert!(!detection.pinned);
+    }
+
+    #[test]
+    fn dates_that_never_move_are_a_coin_toss() {
+        let detection = detect(&["[3/1/17, 11:36:03 PM] Sam: hi", "[3/1/17, 11:37:10 PM] Bob: hey"]);
+        assert_eq!(detection.format.layout, Layout::Ios);
+        assert_eq!(detection.format.date_order, DateOrder::MonthFirst);
+        assert_eq!(detection.format.clock, Clock::H12);
+        assert_eq!(detection.date_order_confidence, 0.5);
+
+        // Dotted dates are day-first in most locales
+        let detection = detect(&["03.01.17, 23:36 - Sam: hi", "03.01.17, 23:37 - Bob: hey"]);
+        assert_eq!(detection.format.date_order, DateOrder::DayFirst);
+        assert_eq!(detection.date_order_confidence, 0.5);
+    }
+
+    #[test]
+    fn a_date_invalid_in_one_order_settles_it() {
+        let dates = ["3/1/17", "3/13/17"];
+        let guess = best_fit(fit(DateOrder::MonthFirst, &dates), fit(DateOrder::DayFirst, &dates)).unwrap();
+        assert_eq!(guess.order, DateOrder::MonthFirst);
+        assert_eq!(guess.confidence, 1.0);
+    }
+
+    #[test]
+    fn confidence_grows_with_the_gap_in_backward_steps() {
+        // Day-first goes back once (Feb 5 to Feb 4), month-first three times
+        let dates = ["01/02/21", "05/02/21", "04/02/21", "10/02/21", "01/03/21", "12/03/21", "01/04/21"];
+        let month_first = fit(DateOrder::MonthFirst, &dates);
+        let day_first = fit(DateOrder::DayFirst, &dates);
+        assert_eq!((month_first.backwards, day_first.backwards), (3, 1));
+        let guess = best_fit(month_first, day_first).unwrap();
+        assert_eq!(guess.order, DateOrder::DayFirst);
+        assert_eq!(guess.confidence, 0.75);
+    }
+
+    #[test]
+    fn a_pinned_format_keeps_the_clock_of_the_file() {
+        let mut detector = Detector::new();
+        detector.push("[3/1/17, 11:36:03 PM] Sam: hi");
+        let detection = detector.finish(FormatArg::AndroidEu);
+        assert_eq!(detection.format.layout, Layout::Android);
+        assert_eq!(detection.format.date_order, DateOrder::DayFirst);
+        assert_eq!(detection.format.clock, Clock::H12);
+        assert_eq!(detection.date_order_confidence, 1.0);
+        assert!(detection.pinned);
+    }
+
+    #[test]
+    fn both_layouts_split_a_header_the_same_way() {
+        let matcher = LineMatcher::new();
+        let ios = matcher.header(Layout::Ios, "[3/1/17, 11:36:03 PM] Sam: hi").unwrap();
+        let android = matcher.header(Layout::Android, "3/1/17, 23:36 - Sam: hi").unwrap();
+        assert_eq!((ios.date, ios.time, ios.meridiem, ios.rest), ("3/1/17", "11:36:03", Some("PM"), "Sam: hi"));
+        assert_eq!((android.date, android.time, android.meridiem, android.rest), ("3/1/17", "23:36", None, "Sam: hi"));
+        assert!(matcher.header(Layout::Android, "[3/1/17, 11:36:03 PM] Sam: hi").is_none());
+        assert!(matcher.header(Layout::Ios, "3/1/17, 23:36 - Sam: hi").is_none());
+    }
+
+    #[test]
+    fn both_layouts_read_into_the_same_message() {
+        let read = |layout, clock, chat: &str| {
+            let format = Format { layout, date_order: DateOrder::MonthFirst, clock, style: Style::default() };
+            let mut reader = MessageReader::new(chat.as_bytes(), ParseOptions::new(format), "chat.txt");
+            let message = reader.next().unwrap().unwrap();
+            assert!(reader.next().is_none());
+            message
+        };
+        let ios = read(Layout::Ios, Clock::H12, "[3/1/17, 11:36:03 PM] Sam: hi\nhow are you?\n");
+        let android = read(Layout::Android, Clock::H24, "3/1/17, 23:36:03 - Sam: hi\nhow are you?\n");
+        assert_eq!(ios.timestamp, android.timestamp);
+        assert_eq!(ios.timestamp.to_rfc3339(), "2017-03-01T23:36:03+00:00");
+        assert_eq!((ios.author.as_str(), ios.text.as_str()), ("Sam", "hi\nhow are you?"));
+        assert_eq!((android.author.as_str(), android.text.as_str()), ("Sam", "hi\nhow are you?"));
+        assert_eq!((ios.kind, android.kind), (MessageKind::Text, MessageKind::Text));
+    }
+
+    #[test]
+    fn times_on_either_clock() {
+        let time = |h, m| NaiveTime::from_hms_opt(h, m, 0);
+        let cases = [
+            ("23:36", None, Clock::H24, time(23, 36)),
+            ("23.36", None, Clock::H24, time(23, 36)),
+            ("11:36", Some("PM"), Clock::H12, time(23, 36)),
+            ("11:36", Some("am"), Clock::H12, time(11, 36)),
+            ("12:05", Some("AM"), Clock::H12, time(0, 5)),
+            ("12:05", Some("PM"), Clock::H12, time(12, 5)),
+            ("11:36", Some("p. m."), Clock::H12, time(23, 36)),
+            ("11:36", Some("p.\u{202F}m."), Clock::H12, time(23, 36)),
+            ("9:00", Some("vorm."), Clock::H12, time(9, 0)),
+            ("9:00", Some("nachm."), Clock::H12, time(21, 0)),
+            ("11:36", Some("午後"), Clock::H12, time(23, 36)),
+            ("13:00", Some("PM"), Clock::H12, None),
+            ("0:30", Some("AM"), Clock::H12, None),
+            ("11:36", None, Clock::H12, None),
+            ("11:36", Some("PM"), Clock::H24, None),
+        ];
+        for (text, meridiem, clock, expected) in cases {
+            assert_eq!(parse_time(text, meridiem, clock), expected, "{} {:?}", text, meridiem);
+        }
+    }
+
+    #[test]
+    fn meridiems_around_the_time() {
+        let matcher = LineMatcher::new();
+        let cases = [
+            ("[3/1/17, 11:36:03 PM] Sam: hi", "PM"),
+            ("[3/1/17, 11:36:03\u{202F}PM] Sam: hi", "PM"),
+            ("[1/3/17, 11:36:03 p. m.] Sam: hi", "p. m."),
+            ("[1/3/17, 11:36:03\u{202F}p.\u{202F}m.] Sam: hi", "p.\u{202F}m."),
+            ("[01.03.17, 11:36:03 nachm.] Sam: hi", "nachm."),
+            ("[2017/03/01 午後11:36:03] Sam: hi", "午後"),
+        ];
+        for (line, meridiem) in cases {
+            let header = matcher.header(Layout::Ios, line).unwrap_or_else(|| panic!("{}", line));
+            assert_eq!((header.time, header.meridiem, header.rest), ("11:36:03", Some(meridiem), "Sam: hi"), "{}", line);
+            assert_eq!(is_pm(meridiem), Some(true), "{}", line);
+        }
+        assert_eq!(is_pm("vorm."), Some(false));
+        assert_eq!(is_pm("noon"), None);
+    }
+}
@@ -0,0 +1,82 @@
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, Timelike};
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// The periods stats can be split into
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum GroupBy {
+    /// Calendar years
+    Year,
+    /// Calendar quarters
+    Quarter,
+    /// Calendar months
+    Month,
+    /// ISO 8601 weeks, from Monday, numbered within their ISO year
+    Week,
+    /// Calendar days
+    Day,
+    /// The day of the week, over all weeks
+    Weekday,
+    /// The hour of the day, over all days
+    Hour,
+}
+
+/// The period a message falls in, in the timezone it is shown in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Bucket {
+    /// A year: `2023`
+    Year(i32),
+    /// A year and quarter from 1: `2023-Q1`
+    Quarter(i32, u32),
+    /// A year and month from 1: `2023-03`
+    Month(i32, u32),
+    /// An ISO year and week: `2023-W09`. The first days of January can be in the last year's week.
+    Week(i32, u32),
+    /// A day: `2023-03-01`
+    Day(NaiveDate),
+    /// A day of the week, counted in days from Monday: `Monday`
+    Weekday(u32),
+    /// An hour of the day: `13:00`
+    Hour(u32),
+}
+
+impl GroupBy {
+    /// The bucket `timestamp` falls in
+    pub fn bucket(self, timestamp: &DateTime<FixedOffset>) -> Bucket {
+        match self {
+            GroupBy::Year => Bucket::Year(timestamp.year()),
+            GroupBy::Quarter => Bucket::Quarter(timestamp.year(), timestamp.month0() / 3 + 1),
+            GroupBy::Month => Bucket::Month(timestamp.year(), timestamp.month()),
+            GroupBy::Week => {
+                let week = timestamp.iso_week();
+                Bucket::Week(week.year(), week.week())
+            }
+            GroupBy::Day => Bucket::Day(timestamp.date_naive()),
+            GroupBy::Weekday => Bucket::Weekday(timestamp.weekday().num_days_from_monday()),
+            GroupBy::Hour => Bucket::Hour(timestamp.hour()),
+        }
+    }
+}
+
+impl fmt::Display for Bucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // e.g. `2023`, `2023-Q1`, `2023-03`, `2023-W09`, `2023-03-01`, `Monday`, `13:00`
+        const WEEKDAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+        match self {
+            Bucket::Year(year) => write!(f, "{}", year),
+            Bucket::Quarter(year, quarter) => write!(f, "{}-Q{}", year, quarter),
+            Bucket::Month(year, month) => write!(f, "{}-{:02}", year, month),
+            Bucket::Week(year, week) => write!(f, "{}-W{:02}", year, week),
+            Bucket::Day(day) => write!(f, "{}", day.format("%Y-%m-%d")),
+            Bucket::Weekday(day) => f.write_str(WEEKDAYS[*day as usize]),
+            Bucket::Hour(hour) => write!(f, "{:02}:00", hour),
+        }
+    }
+}
+
+impl Serialize for Bucket {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
@@ -0,0 +1,182 @@
+//! Any chat flattened to a CSV or TSV table with a row per message, e.g. a
+//! Slack or Discord export run through a script. Which columns hold the
+//! timestamp, author and text, and how the timestamp is written, is given in
+//! a [`CsvFormat`].
+
+use super::{ImportOptions, ImportedChat};
+use crate::error::Error;
+use crate::message::{Message, MessageKind};
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use std::io::BufRead;
+
+/// Timestamps tried in turn after RFC 3339 when no format is given: a plain
+/// date and time, and seconds since the epoch (Slack's `ts`)
+const DEFAULT_TIME_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S", "%s%.f"];
+
+/// Where the fields of a message are in a table. Columns are given by their
+/// header, or by their position counting from 1.
+#[derive(Debug, Clone)]
+pub struct CsvFormat {
+    /// The column of the time a message was sent
+    pub timestamp: String,
+    /// The column of who wrote a message
+    pub author: String,
+    /// The column of what was written
+    pub text: String,
+    /// A chrono format string like `%d.%m.%Y %H:%M`. Times without an offset
+    /// are in the import's timezone.
+    pub time_format: Option<String>,
+}
+
+impl Default for CsvFormat {
+    fn default() -> Self {
+        CsvFormat {
+            timestamp: "timestamp".to_string(),
+            author: "author".to_string(),
+            text: "text".to_string(),
+            time_format: None,
+        }
+    }
+}
+
+fn delimiter(header: &[u8]) -> u8 {
+    // TSV if the header has more tabs than commas
+    let count = |c: u8| header.iter().filter(|&&b| b == c).count();
+    if count(b'\t') > count(b',') { b'\t' } else { b',' }
+}
+
+fn column(headers: &::csv::StringRecord, column: &str, source: &str) -> Result<usize, Error> {
+    let name = column.trim();
+    if let Some(i) = headers.iter().position(|h| h.trim().eq_ignore_ascii_case(name)) {
+        return Ok(i);
+    }
+    match name.parse::<usize>() {
+        Ok(n) if (1..=headers.len()).contains(&n) => Ok(n - 1),
+        _ => {
+            let headers = headers.iter().collect::<Vec<_>>().join(", ");
+            Err(Error::Import(format!("{}: no column \"{}\" (the columns are {})", source, name, headers)))
+        }
+    }
+}
+
+fn timestamp(
+    field: &str,
+    format: Option<&str>,
+    options: &ImportOptions,
+    previous: Option<DateTime<FixedOffset>>,
+) -> Option<DateTime<FixedOffset>> {
+    let field = field.trim();
+    if format.is_none() {
+        // Also takes `Z` for UTC, which a format string can't
+        if let Ok(time) = DateTime::parse_from_rfc3339(field) {
+            return Some(options.time_from_utc(time));
+        }
+    }
+    let given = format.map(|f| [f]);
+    let formats = given.as_ref().map_or(&DEFAULT_TIME_FORMATS[..], |f| &f[..]);
+    for format in formats {
+        if let Ok(time) = DateTime::parse_from_str(field, format) {
+            return Some(options.time_from_utc(time));
+        }
+        if let Ok(naive) = NaiveDateTime::parse_from_str(field, format) {
+            return Some(options.time_from_local(naive, previous));
+        }
+    }
+    None
+}
+
+/// Read a CSV or TSV table of messages, with a header row. Tabs or commas are
+/// told apart by the header.
+pub fn read<R: BufRead>(mut reader: R, format: &CsvFormat, options: &ImportOptions, source: &str) -> Result<ImportedChat, Error> {
+    let error = |e: ::csv::Error| Error::Import(format!("{}: {}", source, e));
+    let delimiter = delimiter(reader.fill_buf()?.split(|&b| b == b'\n').next().unwrap_or_default());
+    let mut table = ::csv::ReaderBuilder::new().delimiter(delimiter).flexible(true).from_reader(reader);
+    let headers = table.headers().map_err(error)?.clone();
+    let timestamp_column = column(&headers, &format.timestamp, source)?;
+    let author_column = column(&headers, &format.author, source)?;
+    let text_column = column(&headers, &format.text, source)?;
+
+    let rows = table.records().collect::<Result<Vec<_>, _>>().map_err(error)?;
+    let mut previous = None;
+    ImportedChat::new(source, None, rows, options, |row, source| {
+        let timestamp = timestamp(row.get(timestamp_column)?, format.time_format.as_deref(), options, previous)?;
+        previous = Some(timestamp);
+        Some(vec![Message {
+            timestamp,
+            author: row.get(author_column)?.trim().to_string(),
+            text: row.get(text_column)?.to_string(),
+            kind: MessageKind::Text,
+            attachment: None,
+            edited: false,
+            source: source.clone(),
+        }])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::ChatSource;
+
+    fn options(timezone: chrono_tz::Tz) -> ImportOptions {
+        ImportOptions { timezone, display_timezone: None, strict: false, normalize: true }
+    }
+
+    fn read_all(table: &str, format: &CsvFormat, options: &ImportOptions) -> Vec<Message> {
+        read(table.as_bytes(), format, options, "chat.csv").unwrap().collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn columns_are_found_by_name_or_position() {
+        let headers = ::csv::StringRecord::from(vec!["When", " User ", "Message"]);
+        assert_eq!(column(&headers, "user", "chat.csv").unwrap(), 1);
+        assert_eq!(column(&headers, "3", "chat.csv").unwrap(), 2);
+        assert!(matches!(column(&headers, "4", "chat.csv"), Err(Error::Import(_))));
+        assert!(matches!(column(&headers, "text", "chat.csv"), Err(Error::Import(_))));
+    }
+
+    #[test]
+    fn tabs_or_commas_are_told_apart_by_the_header() {
+        assert_eq!(delimiter(b"timestamp\tauthor\ttext"), b'\t');
+        assert_eq!(delimiter(b"timestamp,author,text"), b',');
+
+        let format = CsvFormat { timestamp: "1".to_string(), author: "2".to_string(), text: "3".to_string(), time_format: None };
+        let messages = read_all("ts\tname\tbody\n2021-01-01 12:00:00\tAlice\thi, there\n", &format, &options(chrono_tz::UTC));
+        assert_eq!((messages[0].author.as_str(), messages[0].text.as_str()), ("Alice", "hi, there"));
+    }
+
+    #[test]
+    fn rfc_3339_times_keep_their_offset() {
+        let options = options(chrono_tz::Europe::Berlin);
+        let messages = read_all("timestamp,author,text\n2021-01-01T12:00:00Z,Alice,hi\n", &CsvFormat::default(), &options);
+        assert_eq!(messages[0].timestamp.to_rfc3339(), "2021-01-01T13:00:00+01:00");
+    }
+
+    #[test]
+    fn unix_seconds_are_utc_whatever_the_timezone() {
+        let options = options(chrono_tz::Europe::Berlin);
+        let messages = read_all("timestamp,author,text\n1609502400.000200,Alice,hi\n", &CsvFormat::default(), &options);
+        assert_eq!(messages[0].timestamp.to_rfc3339(), "2021-01-01T13:00:00.000200+01:00");
+    }
+
+    #[test]
+    fn times_in_a_given_format_are_in_the_timezone() {
+        let format = CsvFormat { time_format: Some("%d.%m.%Y %H:%M".to_string()), ..CsvFormat::default() };
+        let messages = read_all("timestamp,author,text\n01.07.2021 12:00,Alice,hi\n", &format, &options(chrono_tz::Europe::Berlin));
+        assert_eq!(messages[0].timestamp.to_rfc3339(), "2021-07-01T12:00:00+02:00");
+    }
+
+    #[test]
+    fn rows_that_cant_be_read_are_skipped() {
+        let table = "timestamp,author,text\nyesterday,Alice,hi\n2021-01-01 12:00:00,Bob\n2021-01-01 12:01:00,Bob,hey\n";
+        let chat = read(table.as_bytes(), &CsvFormat::default(), &options(chrono_tz::UTC), "chat.csv").unwrap();
+        assert_eq!(chat.report().skipped_records, 2);
+        let messages: Vec<_> = chat.collect::<Result<_, _>>().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].text, "hey");
+
+        let strict = ImportOptions { strict: true, ..options(chrono_tz::UTC) };
+        let result = read(table.as_bytes(), &CsvFormat::default(), &strict, "chat.csv");
+        assert!(matches!(result, Err(Error::Import(e)) if e == "chat.csv: message 1 can't be read"));
+    }
+}
@@ -0,0 +1,180 @@
+//! Facebook Messenger and Instagram chats from Meta's "Download your
+//! information", the `message_1.json`, `message_2.json`, ... files of a thread.
+//! Both the classic format and the newer one of end-to-end encrypted chats are read.
+
+use super::{json_error, ImportOptions, ImportedChat};
+use crate::error::Error;
+use crate::media::{Attachment, AttachmentKind};
+use crate::message::{Message, MessageKind};
+use crate::system::SystemEvent;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+struct Export {
+    #[serde(alias = "threadName")]
+    title: Option<String>,
+    messages: Vec<Record>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Record {
+    #[serde(alias = "senderName")]
+    sender_name: Option<String>,
+    #[serde(alias = "timestamp")]
+    timestamp_ms: Option<i64>,
+    #[serde(alias = "text")]
+    content: Option<String>,
+    #[serde(rename = "type")]
+    kind: Option<String>, // "Generic", "Share", "Call", "Subscribe", "Unsubscribe", ...
+    #[serde(default)]
+    is_unsent: bool,
+    #[serde(default)]
+    reactions: Vec<Reaction>,
+    #[serde(default)]
+    photos: Vec<Media>,
+    #[serde(default)]
+    videos: Vec<Media>,
+    #[serde(default)]
+    audio_files: Vec<Media>,
+    #[serde(default)]
+    gifs: Vec<Media>,
+    #[serde(default)]
+    files: Vec<Media>,
+    sticker: Option<Media>,
+    #[serde(default)]
+    media: Vec<Media>, // the newer format's attachments, of any kind
+}
+
+#[derive(Debug, Deserialize)]
+struct Reaction {
+    reaction: String,
+    actor: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Media {
+    uri: String, // path of the file in the download
+}
+
+/// Whether a JSON export looks like it came from Messenger or Instagram
+pub fn matches(value: &Value) -> bool {
+    value.get("participants").is_some()
+        && match value.get("messages").and_then(Value::as_array) {
+            Some(messages) => messages.first().is_none_or(|m| m.get("sender_name").is_some() || m.get("senderName").is_some()),
+            None => false,
+        }
+}
+
+/// Undo Meta's double encoding: the UTF-8 bytes of the text are written as if
+/// each byte were a Latin-1 character, so `é` comes out as `Ã©`
+fn fix_encoding(text: String) -> String {
+    if text.is_ascii() || text.chars().any(|c| c as u32 > 0xFF) {
+        return text;
+    }
+    let bytes: Vec<u8> = text.chars().map(|c| c as u8).collect();
+    String::from_utf8(bytes).unwrap_or(text)
+}
+
+fn file_name(media: Media) -> Option<String> {
+    media.uri.rsplit('/').next().filter(|f| !f.is_empty()).map(|f| fix_encoding(f.to_string()))
+}
+
+fn attachment(record: &mut Record) -> Option<Attachment> {
+    let kinds = [
+        (&mut record.photos, AttachmentKind::Image),
+        (&mut record.videos, AttachmentKind::Video),
+        (&mut record.audio_files, AttachmentKind::Audio),
+        (&mut record.gifs, AttachmentKind::Gif),
+        (&mut record.files, AttachmentKind::Document),
+    ];
+    for (media, kind) in kinds {
+        if !media.is_empty() {
+            let file = file_name(media.swap_remove(0));
+            return Some(Attachment { kind, file, size: None });
+        }
+    }
+    if let Some(sticker) = record.sticker.take() {
+        return Some(Attachment { kind: AttachmentKind::Sticker, file: file_name(sticker), size: None });
+    }
+    if !record.media.is_empty() {
+        let file = file_name(record.media.swap_remove(0));
+        let kind = file.as_deref().map_or(AttachmentKind::Unknown, AttachmentKind::from_file_name);
+        return Some(Attachment { kind, file, size: None });
+    }
+    None
+}
+
+fn is_reaction_notice(text: &str) -> bool {
+    // Instagram writes some reactions as messages of their own
+    text == "Liked a message" || (text.starts_with("Reacted ") && text.ends_with(" to your message"))
+}
+
+fn messages(mut record: Record, source: &Arc<str>, options: &ImportOptions) -> Option<Vec<Message>> {
+    let timestamp = options.time_from_millis(record.timestamp_ms?)?;
+    let author = fix_encoding(record.sender_name.take().unwrap_or_default());
+    let text = fix_encoding(record.content.take().unwrap_or_default());
+    let system = |event, text| Message {
+        timestamp,
+        author: author.clone(),
+        text,
+        kind: MessageKind::System(event),
+        attachment: None,
+        edited: false,
+        source: source.clone(),
+    };
+
+    let mut messages = Vec::with_capacity(1 + record.reactions.len());
+    match record.kind.as_deref() {
+        Some("Subscribe") => messages.push(system(SystemEvent::Added, text)),
+        Some("Unsubscribe") if text.ends_with(" left the group.") => messages.push(system(SystemEvent::Left, text)),
+        Some("Unsubscribe") => messages.push(system(SystemEvent::Removed, text)),
+        Some("Call") => messages.push(system(SystemEvent::Other, text)),
+        _ if is_reaction_notice(&text) => messages.push(system(SystemEvent::Reaction, text)),
+        _ if record.is_unsent => messages.push(Message {
+            kind: MessageKind::Deleted,
+            text: String::new(),
+            ..system(SystemEvent::Other, String::new())
+        }),
+        _ => {
+            let attachment = attachment(&mut record);
+            messages.push(Message { kind: MessageKind::Text, attachment, ..system(SystemEvent::Other, text) });
+        }
+    }
+    // Reactions aren't messages, so they are only counted as events, by whoever reacted
+    for reaction in record.reactions {
+        let actor = fix_encoding(reaction.actor);
+        let text = format!("{} reacted {}", actor, fix_encoding(reaction.reaction));
+        messages.push(Message { author: actor, ..system(SystemEvent::Reaction, text) });
+    }
+    Some(messages)
+}
+
+/// Read one `message_N.json` of a Messenger or Instagram thread. Long threads
+/// are split over several files, which can be passed together.
+pub fn read(value: Value, options: &ImportOptions, source: &str) -> Result<ImportedChat, Error> {
+    let export: Export = serde_json::from_value(value).map_err(|e| json_error(source, e))?;
+    let chat_name = export.title.map(fix_encoding);
+    ImportedChat::new(source, chat_name, export.messages, options, |record, source| messages(record, source, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_encoded_text_is_restored() {
+        assert_eq!(fix_encoding("CafÃ©".to_string()), "Café");
+        assert_eq!(fix_encoding("\u{F0}\u{9F}\u{98}\u{80}".to_string()), "😀");
+    }
+
+    #[test]
+    fn text_that_isnt_double_encoded_is_left_alone() {
+        assert_eq!(fix_encoding("plain".to_string()), "plain");
+        // Not valid UTF-8 once read as bytes
+        assert_eq!(fix_encoding("Café".to_string()), "Café");
+        // Beyond Latin-1, so it can't have been written byte by byte
+        assert_eq!(fix_encoding("Привет".to_string()), "Привет");
+    }
+}
@@ -0,0 +1,152 @@
+//! Importers for chat exports from messengers other than WhatsApp. Their
+//! exports are structured files that are read whole, then handed out a message
+//! at a time like a WhatsApp chat.
+
+pub mod csv;
+pub mod messenger;
+pub mod signal;
+pub mod sms;
+pub mod telegram;
+
+use crate::error::{Error, ParseReport};
+use crate::message::Message;
+use crate::parse;
+use crate::source::ChatSource;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::sync::Arc;
+
+/// How an export from another messenger is read
+#[derive(Debug, Clone, Copy)]
+pub struct ImportOptions {
+    /// The timezone local times in the export are written in, and that
+    /// UTC times are shown in unless `display_timezone` is set
+    pub timezone: Tz,
+    /// The timezone to convert timestamps to
+    pub display_timezone: Option<Tz>,
+    /// Fail on records that can't be read rather than skipping them
+    pub strict: bool,
+    /// Strip invisible marks from authors and text and normalize them to NFC
+    pub normalize: bool,
+}
+
+impl ImportOptions {
+    fn display(&self) -> Tz {
+        self.display_timezone.unwrap_or(self.timezone)
+    }
+
+    /// A time given in milliseconds since the epoch
+    fn time_from_millis(&self, millis: i64) -> Option<DateTime<FixedOffset>> {
+        let utc = Utc.timestamp_millis_opt(millis).single()?;
+        Some(utc.with_timezone(&self.display()).fixed_offset())
+    }
+
+    /// A time given with its offset
+    fn time_from_utc(&self, time: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        time.with_timezone(&self.display()).fixed_offset()
+    }
+
+    /// A wall clock time in `timezone`
+    fn time_from_local(&self, naive: NaiveDateTime, previous: Option<DateTime<FixedOffset>>) -> DateTime<FixedOffset> {
+        let local = parse::localize(naive, self.timezone, previous);
+        match self.display_timezone {
+            Some(display) => local.with_timezone(&display).fixed_offset(),
+            None => local,
+        }
+    }
+
+    fn clean(&self, text: String) -> String {
+        if self.normalize { parse::normalize_owned(text) } else { text }
+    }
+}
+
+/// A chat read whole from a structured export
+pub struct ImportedChat {
+    source: Arc<str>,
+    chat_name: Option<String>,
+    messages: std::vec::IntoIter<Message>,
+    report: ParseReport,
+}
+
+impl ImportedChat {
+    /// Collects the messages of an export, skipping the records `read` can't
+    /// turn into messages, or failing on the first one in strict mode
+    fn new<T, F>(source: &str, chat_name: Option<String>, records: Vec<T>, options: &ImportOptions, mut read: F) -> Result<Self, Error>
+    where
+        F: FnMut(T, &Arc<str>) -> Option<Vec<Message>>,
+    {
+        let source: Arc<str> = source.into();
+        let mut report = ParseReport::default();
+        let mut messages = Vec::with_capacity(records.len());
+        for (i, record) in records.into_iter().enumerate() {
+            match read(record, &source) {
+                Some(read) => messages.extend(read),
+                None if options.strict => {
+                    return Err(Error::Import(format!("{}: message {} can't be read", source, i + 1)));
+                }
+                None => report.skipped_records += 1,
+            }
+        }
+        ImportedChat::from_messages(source, chat_name, messages, report, options)
+    }
+
+    /// Hands out `messages` in time order, cleaned up as `options` ask
+    fn from_messages(
+        source: Arc<str>,
+        chat_name: Option<String>,
+        mut messages: Vec<Message>,
+        report: ParseReport,
+        options: &ImportOptions,
+    ) -> Result<Self, Error> {
+        if options.strict && !report.is_empty() {
+            return Err(Error::Import(format!("{}: {}", source, report)));
+        }
+        for m in &mut messages {
+            m.author = options.clean(std::mem::take(&mut m.author));
+            m.text = options.clean(std::mem::take(&mut m.text));
+        }
+        // Exports are mostly in time order, but be sure of it as merging depends on it
+        messages.sort_by_key(|m| m.timestamp);
+        let chat_name = chat_name.map(|n| options.clean(n));
+        Ok(ImportedChat { source, chat_name, messages: messages.into_iter(), report })
+    }
+}
+
+impl Iterator for ImportedChat {
+    type Item = Result<Message, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.messages.next().map(Ok)
+    }
+}
+
+impl ChatSource for ImportedChat {
+    fn source(&self) -> &str {
+        &self.source
+    }
+
+    fn report(&self) -> &ParseReport {
+        &self.report
+    }
+
+    fn chat_name(&self) -> Option<&str> {
+        self.chat_name.as_deref()
+    }
+}
+
+fn json_error(source: &str, e: serde_json::Error) -> Error {
+    Error::Import(format!("{}: {}", source, e))
+}
+
+/// Read a JSON export from whichever messenger it looks like it came from
+pub fn read_json(value: serde_json::Value, options: &ImportOptions, source: &str) -> Result<ImportedChat, Error> {
+    if telegram::matches(&value) {
+        telegram::read(value, options, source)
+    } else if messenger::matches(&value) {
+        messenger::read(value, options, source)
+    } else if signal::matches(&value) {
+        signal::read(value, options, source)
+    } else {
+        Err(Error::Import(format!("{}: not a Telegram, Messenger, Instagram or Signal export", source)))
+    }
+}
@@ -0,0 +1,145 @@
+//! Signal backups decrypted to JSON, e.g. with sigtop or signalbackup-tools.
+//! Messages are records of Signal's own message model: a list of them, or an
+//! object with the conversation `name` and its `messages`.
+
+use super::{json_error, ImportOptions, ImportedChat};
+use crate::alias::YOU;
+use crate::error::Error;
+use crate::media::{Attachment, AttachmentKind};
+use crate::message::{Message, MessageKind};
+use crate::system::SystemEvent;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Export {
+    Messages(Vec<Record>),
+    Conversation {
+        name: Option<String>,
+        messages: Vec<Record>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Record {
+    #[serde(rename = "type")]
+    kind: Option<String>, // "incoming", "outgoing" or the kind of notification
+    #[serde(rename = "sent_at")]
+    sent_at: Option<i64>, // milliseconds since the epoch
+    timestamp: Option<i64>, // the same, in older exports
+    body: Option<String>,
+    source_name: Option<String>, // the sender's profile or contact name, when the export resolved it
+    source: Option<String>, // the sender's phone number
+    source_service_id: Option<String>,
+    source_uuid: Option<String>,
+    #[serde(default)]
+    attachments: Vec<SignalAttachment>,
+    sticker: Option<Value>,
+    #[serde(default)]
+    contact: Vec<Value>, // shared contacts
+    #[serde(default)]
+    deleted_for_everyone: bool,
+    #[serde(default)]
+    is_erased: bool,
+    #[serde(default)]
+    edit_history: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SignalAttachment {
+    content_type: Option<String>,
+    file_name: Option<String>,
+    size: Option<u64>,
+    #[serde(default)]
+    flags: u32, // 1 for voice messages
+}
+
+const VOICE_MESSAGE: u32 = 1;
+
+/// Whether a JSON export looks like it came from Signal
+pub fn matches(value: &Value) -> bool {
+    let messages = match value {
+        Value::Array(messages) => messages,
+        Value::Object(_) => match value.get("messages").and_then(Value::as_array) {
+            Some(messages) => messages,
+            None => return false,
+        },
+        _ => return false,
+    };
+    messages.first().is_none_or(|m| m.get("sent_at").is_some() || m.get("timestamp").is_some())
+}
+
+fn attachment(record: &mut Record) -> Option<Attachment> {
+    if record.sticker.is_some() {
+        return Some(Attachment { kind: AttachmentKind::Sticker, file: None, size: None });
+    }
+    if !record.contact.is_empty() {
+        return Some(Attachment { kind: AttachmentKind::Contact, file: None, size: None });
+    }
+    if record.attachments.is_empty() {
+        return None;
+    }
+    let a = record.attachments.swap_remove(0);
+    let content_type = a.content_type.unwrap_or_default();
+    let kind = match AttachmentKind::from_content_type(&content_type) {
+        AttachmentKind::Audio if a.flags & VOICE_MESSAGE != 0 => AttachmentKind::VoiceNote,
+        kind => kind,
+    };
+    Some(Attachment { kind, file: a.file_name, size: a.size })
+}
+
+fn notification(kind: &str) -> (SystemEvent, &'static str) {
+    match kind {
+        "timer-notification" => (SystemEvent::DisappearingMessages, "changed disappearing messages"),
+        "keychange" | "verified-change" => (SystemEvent::SecurityCodeChanged, "safety number changed"),
+        "change-number-notification" => (SystemEvent::NumberChanged, "changed their phone number"),
+        "group-v2-change" | "group" => (SystemEvent::SettingsChanged, "changed the group"),
+        _ => (SystemEvent::Other, ""),
+    }
+}
+
+fn message(mut record: Record, source: &Arc<str>, options: &ImportOptions) -> Option<Message> {
+    let timestamp = options.time_from_millis(record.sent_at.or(record.timestamp)?)?;
+    let source = source.clone();
+    let kind = record.kind.take().unwrap_or_else(|| "incoming".to_string());
+    // The exporter's own messages are "You", like in WhatsApp exports, so that --me applies
+    let author = match kind.as_str() {
+        "outgoing" => YOU.to_string(),
+        _ => record
+            .source_name
+            .take()
+            .or(record.source.take())
+            .or(record.source_service_id.take())
+            .or(record.source_uuid.take())
+            .unwrap_or_default(),
+    };
+    match kind.as_str() {
+        "incoming" | "outgoing" => {
+            let deleted = record.deleted_for_everyone || record.is_erased;
+            let attachment = if deleted { None } else { attachment(&mut record) };
+            let kind = if deleted { MessageKind::Deleted } else { MessageKind::Text };
+            let text = if deleted { String::new() } else { record.body.unwrap_or_default() };
+            let edited = !record.edit_history.is_empty();
+            Some(Message { timestamp, author, text, kind, attachment, edited, source })
+        }
+        other => {
+            let (event, description) = notification(other);
+            let text = record.body.unwrap_or_else(|| format!("{} {}", author, description).trim().to_string());
+            Some(Message { timestamp, author, text, kind: MessageKind::System(event), attachment: None, edited: false, source })
+        }
+    }
+}
+
+/// Read a decrypted Signal export
+pub fn read(value: Value, options: &ImportOptions, source: &str) -> Result<ImportedChat, Error> {
+    let export: Export = serde_json::from_value(value).map_err(|e| json_error(source, e))?;
+    let (chat_name, records) = match export {
+        Export::Messages(records) => (None, records),
+        Export::Conversation { name, messages } => (name, messages),
+    };
+    ImportedChat::new(source, chat_name, records, options, |record, source| message(record, source, options).map(|m| vec![m]))
+}
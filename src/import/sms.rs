@@ -0,0 +1,348 @@
+//! Text messages backed up by the Android app SMS Backup & Restore: an XML file
+//! of `<sms>` and `<mms>` elements for every conversation on the phone.
+//! Conversations ("threads") are told apart by the phone numbers in them.
+
+use super::{ImportOptions, ImportedChat};
+use crate::alias::YOU;
+use crate::error::{Error, ParseReport};
+use crate::media::{Attachment, AttachmentKind};
+use crate::message::{Message, MessageKind};
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufRead;
+use std::sync::Arc;
+
+const MMS_FROM: &str = "137"; // the type of the `<addr>` that sent an MMS
+
+// The box a message is in, the `type` of an SMS or `msg_box` of an MMS.
+// Messages in any other box were sent from the phone.
+const RECEIVED: &str = "1";
+const DRAFT: &str = "3";
+
+/// A conversation in a backup
+#[derive(Debug, Clone)]
+pub struct Thread {
+    /// The phone numbers of everyone in it besides the phone's owner
+    pub numbers: Vec<String>,
+    /// Their contact names, as the backup gives them
+    pub name: String,
+    /// How many messages it has
+    pub num_messages: u64,
+}
+
+/// A message as it is in the backup
+#[derive(Debug)]
+struct Record {
+    thread: Vec<String>, // normalized numbers of the others in the conversation
+    sender: Option<String>, // normalized number of the sender, None when it was the phone's owner
+    contact_name: Option<String>,
+    date: i64, // milliseconds since the epoch
+    text: String,
+    attachment: Option<Attachment>,
+}
+
+fn normalize_number(number: &str) -> String {
+    // `+1 (555) 123-4567` and `5551234567` are the same number: compare the last ten digits
+    let digits: String = number.chars().filter(char::is_ascii_digit).collect();
+    match digits.len() {
+        0 => number.trim().to_string(),
+        n if n > 10 => digits[n - 10..].to_string(),
+        _ => digits,
+    }
+}
+
+fn attributes(e: &BytesStart, source: &str) -> Result<HashMap<String, String>, Error> {
+    let error = |e: &dyn std::fmt::Display| Error::Import(format!("{}: {}", source, e));
+    let mut attributes = HashMap::new();
+    for attribute in e.attributes() {
+        let attribute = attribute.map_err(|e| error(&e))?;
+        let key = attribute.key.as_ref().to_string();
+        // The app writes "null" for missing values
+        if attribute.value == "null" {
+            continue;
+        }
+        let value = quick_xml::escape::unescape(&attribute.value).map_err(|e| error(&e))?.into_owned();
+        attributes.insert(key, value);
+    }
+    Ok(attributes)
+}
+
+fn base64_size(data: &str) -> u64 {
+    let padding = data.bytes().rev().take_while(|&b| b == b'=').count();
+    (data.len() / 4 * 3).saturating_sub(padding) as u64
+}
+
+/// Every message in a backup, in the order it has them. Drafts are left out,
+/// and so are messages without a date, counting them in `report`.
+fn records<R: BufRead>(reader: R, source: &str, report: &mut ParseReport) -> Result<Vec<Record>, Error> {
+    let mut reader = Reader::from_reader(reader);
+    let mut buf = Vec::new();
+    let mut records = Vec::new();
+    let mut mms: Option<(HashMap<String, String>, Record)> = None; // the MMS whose parts are being read
+
+    loop {
+        buf.clear();
+        let event = reader.read_event_into(&mut buf).map_err(|e| Error::Import(format!("{}: {}", source, e)))?;
+        match event {
+            Event::Eof => break,
+            Event::Empty(e) if e.name().as_ref() == "sms" => {
+                let a = attributes(&e, source)?;
+                let (Some(date), Some(address)) = (a.get("date").and_then(|d| d.parse().ok()), a.get("address")) else {
+                    report.skipped_records += 1;
+                    continue;
+                };
+                let sent = match a.get("type").map(String::as_str) {
+                    Some(RECEIVED) => false,
+                    Some(DRAFT) => continue,
+                    _ => true,
+                };
+                let number = normalize_number(address);
+                records.push(Record {
+                    thread: vec![number.clone()],
+                    sender: if sent { None } else { Some(number) },
+                    contact_name: a.get("contact_name").cloned(),
+                    date,
+                    text: a.get("body").cloned().unwrap_or_default(),
+                    attachment: None,
+                });
+            }
+            Event::Start(e) if e.name().as_ref() == "mms" => {
+                let a = attributes(&e, source)?;
+                let mut thread: Vec<String> = a.get("address").map_or(Vec::new(), |a| a.split('~').map(normalize_number).collect());
+                thread.sort();
+                thread.dedup();
+                let record = Record {
+                    thread,
+                    sender: None,
+                    contact_name: a.get("contact_name").cloned(),
+                    date: -1,
+                    text: String::new(),
+                    attachment: None,
+                };
+                mms = Some((a, record));
+            }
+            Event::Empty(e) if e.name().as_ref() == "part" => {
+                let Some((_, record)) = &mut mms else { continue };
+                let a = attributes(&e, source)?;
+                let content_type = a.get("ct").map_or("", String::as_str);
+                match content_type {
+                    "application/smil" => {}
+                    "text/plain" => {
+                        if !record.text.is_empty() {
+                            record.text.push('\n');
+                        }
+                        record.text.push_str(a.get("text").map_or("", String::as_str));
+                    }
+                    _ if record.attachment.is_none() => {
+                        let file = a.get("cl").or(a.get("name")).cloned();
+                        let size = a.get("data").map(|d| base64_size(d));
+                        record.attachment = Some(Attachment { kind: AttachmentKind::from_content_type(content_type), file, size });
+                    }
+                    _ => {}
+                }
+            }
+            Event::Empty(e) if e.name().as_ref() == "addr" => {
+                let Some((_, record)) = &mut mms else { continue };
+                let a = attributes(&e, source)?;
+                if a.get("type").map(String::as_str) == Some(MMS_FROM) {
+                    record.sender = a.get("address").map(|a| normalize_number(a));
+                }
+            }
+            Event::End(e) if e.name().as_ref() == "mms" => {
+                let Some((a, mut record)) = mms.take() else { continue };
+                // MMS dates are in seconds in some versions of the app
+                let date = a.get("date").and_then(|d| d.parse::<i64>().ok()).and_then(|d| if d < 100_000_000_000 { d.checked_mul(1000) } else { Some(d) });
+                let msg_box = a.get("msg_box").map(String::as_str);
+                if msg_box == Some(DRAFT) {
+                    continue;
+                }
+                let Some(date) = date else {
+                    report.skipped_records += 1;
+                    continue;
+                };
+                record.date = date;
+                if msg_box != Some(RECEIVED) {
+                    record.sender = None;
+                }
+                records.push(record);
+            }
+            _ => {}
+        }
+    }
+    Ok(records)
+}
+
+/// The contact names of numbers, from the conversations with just them
+fn contact_names(records: &[Record]) -> HashMap<&str, &str> {
+    records
+        .iter()
+        .filter(|r| r.thread.len() == 1)
+        .filter_map(|r| {
+            let name = r.contact_name.as_deref().filter(|n| *n != "(Unknown)")?;
+            Some((r.thread[0].as_str(), name))
+        })
+        .collect()
+}
+
+fn group_threads(records: &[Record]) -> BTreeMap<&[String], Thread> {
+    let names = contact_names(records);
+    let mut threads: BTreeMap<&[String], Thread> = BTreeMap::new();
+    for record in records {
+        let thread = threads.entry(&record.thread).or_insert_with(|| {
+            // The app names group conversations too, but only with the contacts it knows
+            let name = match record.contact_name.as_deref().filter(|n| *n != "(Unknown)") {
+                Some(name) => name.to_string(),
+                None => record.thread.iter().map(|n| names.get(n.as_str()).copied().unwrap_or(n)).collect::<Vec<_>>().join(", "),
+            };
+            Thread { numbers: record.thread.clone(), name, num_messages: 0 }
+        });
+        thread.num_messages += 1;
+    }
+    threads
+}
+
+/// The conversations in a backup, most messages first
+pub fn threads<R: BufRead>(reader: R, source: &str) -> Result<Vec<Thread>, Error> {
+    let records = records(reader, source, &mut ParseReport::default())?;
+    let mut threads: Vec<Thread> = group_threads(&records).into_values().collect();
+    threads.sort_by(|a, b| b.num_messages.cmp(&a.num_messages).then_with(|| a.name.cmp(&b.name)));
+    Ok(threads)
+}
+
+impl Thread {
+    /// Whether `selector` picks this thread: part of its name, ignoring case, or one of its numbers
+    pub fn matches(&self, selector: &str) -> bool {
+        let number = normalize_number(selector);
+        self.name.to_lowercase().contains(&selector.to_lowercase()) || self.numbers.contains(&number)
+    }
+}
+
+/// Read the messages of a backup, of the threads `thread` selects or of all of them.
+/// Messages sent from the phone are written by "You".
+pub fn read<R: BufRead>(reader: R, options: &ImportOptions, source: &str, thread: Option<&str>) -> Result<ImportedChat, Error> {
+    let mut report = ParseReport::default();
+    let records = records(reader, source, &mut report)?;
+    let threads = group_threads(&records);
+    let selected: Vec<&Thread> = threads.values().filter(|t| thread.is_none_or(|s| t.matches(s))).collect();
+    if let (Some(selector), true) = (thread, selected.is_empty()) {
+        return Err(Error::Import(format!("{}: no conversation matches \"{}\"", source, selector)));
+    }
+    let chat_name = match selected.as_slice() {
+        [thread] => Some(thread.name.clone()),
+        _ => None,
+    };
+
+    let names = contact_names(&records);
+    let source: Arc<str> = source.into();
+    let mut messages = Vec::new();
+    for record in &records {
+        if !selected.iter().any(|t| t.numbers == record.thread) {
+            continue;
+        }
+        let Some(timestamp) = options.time_from_millis(record.date) else {
+            report.skipped_records += 1;
+            continue;
+        };
+        let author = match &record.sender {
+            None => YOU.to_string(),
+            Some(number) => names.get(number.as_str()).map_or(number.as_str(), |n| n).to_string(),
+        };
+        messages.push(Message {
+            timestamp,
+            author,
+            text: record.text.clone(),
+            kind: MessageKind::Text,
+            attachment: record.attachment.clone(),
+            edited: false,
+            source: source.clone(),
+        });
+    }
+    ImportedChat::from_messages(source, chat_name, messages, report, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::ChatSource;
+
+    const BACKUP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<smses count="5">
+  <sms address="+1 (555) 123-4567" date="1609502400000" type="1" body="Hi there" contact_name="Alice" />
+  <sms address="5551234567" date="1609502460000" type="2" body="Hello Alice" contact_name="Alice" />
+  <sms address="5551234567" date="1609502520000" type="3" body="unsent draft" contact_name="Alice" />
+  <mms date="1609502580" msg_box="1" address="+15551234567~+15559876543" contact_name="null">
+    <parts>
+      <part ct="application/smil" text="null" />
+      <part ct="text/plain" text="group hello" />
+    </parts>
+    <addrs>
+      <addr address="+15559876543" type="151" />
+      <addr address="+15551234567" type="137" />
+    </addrs>
+  </mms>
+  <mms date="-99999999999999999" msg_box="1" address="+15551234567" contact_name="Alice">
+    <parts>
+      <part ct="text/plain" text="from nowhen" />
+    </parts>
+    <addrs>
+      <addr address="+15551234567" type="137" />
+    </addrs>
+  </mms>
+</smses>
+"#;
+
+    fn options() -> ImportOptions {
+        ImportOptions { timezone: chrono_tz::UTC, display_timezone: None, strict: false, normalize: true }
+    }
+
+    fn messages(thread: Option<&str>) -> Vec<Message> {
+        read(BACKUP.as_bytes(), &options(), "sms.xml", thread).unwrap().collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn numbers_are_compared_by_their_last_ten_digits() {
+        assert_eq!(normalize_number("+1 (555) 123-4567"), "5551234567");
+        assert_eq!(normalize_number("5551234567"), "5551234567");
+        assert_eq!(normalize_number("+44 20 7946 0958"), "2079460958");
+        assert_eq!(normalize_number(" Vodafone "), "Vodafone");
+    }
+
+    #[test]
+    fn drafts_are_left_out_and_sent_messages_are_by_you() {
+        // A number picks every conversation with it, the group one too
+        let messages = messages(Some("+1 555 123 4567"));
+        let texts: Vec<_> = messages.iter().map(|m| (m.author.as_str(), m.text.as_str())).collect();
+        assert_eq!(texts, [("Alice", "Hi there"), (YOU, "Hello Alice"), ("Alice", "group hello")]);
+    }
+
+    #[test]
+    fn mms_are_sent_by_their_from_address() {
+        let messages = messages(Some("555-987-6543"));
+        assert_eq!(messages.len(), 1);
+        assert_eq!((messages[0].author.as_str(), messages[0].text.as_str()), ("Alice", "group hello"));
+        // The date is in seconds
+        assert_eq!(messages[0].timestamp.to_rfc3339(), "2021-01-01T12:03:00+00:00");
+    }
+
+    #[test]
+    fn mms_with_dates_out_of_range_are_skipped() {
+        let chat = read(BACKUP.as_bytes(), &options(), "sms.xml", None).unwrap();
+        assert_eq!(chat.report().skipped_records, 1);
+        assert_eq!(chat.count(), 3);
+
+        let strict = ImportOptions { strict: true, ..options() };
+        assert!(matches!(read(BACKUP.as_bytes(), &strict, "sms.xml", None), Err(Error::Import(_))));
+    }
+
+    #[test]
+    fn threads_are_picked_by_name_or_number() {
+        let threads = threads(BACKUP.as_bytes(), "sms.xml").unwrap();
+        let names: Vec<_> = threads.iter().map(|t| (t.name.as_str(), t.num_messages)).collect();
+        assert_eq!(names, [("Alice", 2), ("Alice, 5559876543", 1)]);
+        assert!(threads[0].matches("alice"));
+        assert!(threads[0].matches("+1 555 123 4567"));
+        assert!(!threads[0].matches("5559876543"));
+        assert!(threads[1].matches("(555) 987-6543"));
+    }
+}
@@ -0,0 +1,230 @@
+//! Telegram Desktop's JSON export ("Export chat history" with the
+//! machine-readable JSON format), of one chat or of a whole account.
+
+use super::{json_error, ImportOptions, ImportedChat};
+use crate::error::Error;
+use crate::media::{Attachment, AttachmentKind};
+use crate::message::{Message, MessageKind};
+use crate::system::SystemEvent;
+use chrono::{DateTime, FixedOffset, NaiveDateTime};
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+struct Export {
+    name: Option<String>,
+    #[serde(default)]
+    messages: Vec<Record>,
+    chats: Option<ChatList>, // only in exports of a whole account
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatList {
+    list: Vec<Export>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Record {
+    #[serde(rename = "type")]
+    kind: String, // "message" or "service"
+    date: Option<String>, // local time of the exporting computer: 2021-01-01T12:00:00
+    date_unixtime: Option<String>, // the same in seconds since the epoch, in newer exports
+    from: Option<String>,
+    from_id: Option<String>,
+    actor: Option<String>, // who caused a service message
+    actor_id: Option<String>,
+    action: Option<String>, // what a service message is about, e.g. "invite_members"
+    title: Option<String>,
+    #[serde(default)]
+    members: Vec<Option<String>>,
+    #[serde(default)]
+    text: Text,
+    edited: Option<String>,
+    photo: Option<String>,
+    photo_file_size: Option<u64>,
+    file: Option<String>,
+    file_size: Option<u64>,
+    media_type: Option<String>,
+    contact_information: Option<Value>,
+}
+
+/// Message text is plain, or a list of plain and formatted pieces
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Text {
+    Plain(String),
+    Parts(Vec<Part>),
+}
+
+impl Default for Text {
+    fn default() -> Self {
+        Text::Plain(String::new())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Part {
+    Plain(String),
+    Entity { text: String }, // bold, links, mentions, ...
+}
+
+impl Text {
+    fn into_string(self) -> String {
+        match self {
+            Text::Plain(text) => text,
+            Text::Parts(parts) => parts
+                .into_iter()
+                .map(|p| match p {
+                    Part::Plain(text) | Part::Entity { text } => text,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Whether a JSON export looks like it came from Telegram
+pub fn matches(value: &Value) -> bool {
+    if value.get("chats").is_some_and(|c| c.get("list").is_some()) {
+        return true;
+    }
+    match value.get("messages").and_then(Value::as_array) {
+        Some(messages) => messages.first().is_none_or(|m| m.get("date").is_some() || m.get("date_unixtime").is_some()),
+        None => false,
+    }
+}
+
+fn file_name(file: Option<String>) -> Option<String> {
+    // Files left out of the export are written as `(File not included. Change data exporting settings to download.)`
+    file.filter(|f| !f.starts_with('('))
+}
+
+fn attachment(record: &mut Record) -> Option<Attachment> {
+    if let Some(photo) = record.photo.take() {
+        let file = file_name(Some(photo));
+        return Some(Attachment { kind: AttachmentKind::Image, file, size: record.photo_file_size });
+    }
+    if record.file.is_some() || record.media_type.is_some() {
+        let file = file_name(record.file.take());
+        let kind = match record.media_type.as_deref() {
+            Some("sticker") => AttachmentKind::Sticker,
+            Some("voice_message") => AttachmentKind::VoiceNote,
+            Some("video_message" | "video_file") => AttachmentKind::Video,
+            Some("animation") => AttachmentKind::Gif,
+            Some("audio_file") => AttachmentKind::Audio,
+            Some(_) => AttachmentKind::Document,
+            None => file.as_deref().map_or(AttachmentKind::Document, AttachmentKind::from_file_name),
+        };
+        return Some(Attachment { kind, file, size: record.file_size });
+    }
+    if record.contact_information.is_some() {
+        return Some(Attachment { kind: AttachmentKind::Contact, file: None, size: None });
+    }
+    None
+}
+
+fn service(record: &Record, actor: &str) -> (SystemEvent, String) {
+    // Described the way WhatsApp would put it, e.g. `Alice added Bob, Carol`
+    let members = record.members.iter().flatten().map(String::as_str).collect::<Vec<_>>().join(", ");
+    let title = record.title.as_deref().unwrap_or_default();
+    let action = record.action.as_deref().unwrap_or_default();
+    let (event, description) = match action {
+        "create_group" | "create_channel" | "migrate_from_group" => (SystemEvent::Created, format!("created group \"{}\"", title)),
+        "invite_members" => (SystemEvent::Added, format!("added {}", members)),
+        "remove_members" if members == actor => (SystemEvent::Left, "left".to_string()),
+        "remove_members" => (SystemEvent::Removed, format!("removed {}", members)),
+        "join_group_by_link" | "join_group_by_request" => (SystemEvent::Joined, "joined using an invite link".to_string()),
+        "edit_group_title" => (SystemEvent::SubjectChanged, format!("changed the group name to \"{}\"", title)),
+        "edit_group_photo" => (SystemEvent::IconChanged, "changed the group icon".to_string()),
+        "delete_group_photo" => (SystemEvent::IconChanged, "deleted the group icon".to_string()),
+        "set_messages_ttl" => (SystemEvent::DisappearingMessages, "changed disappearing messages".to_string()),
+        _ => (SystemEvent::Other, action.replace('_', " ")),
+    };
+    (event, format!("{} {}", actor, description).trim().to_string())
+}
+
+fn timestamp(record: &Record, options: &ImportOptions, previous: Option<DateTime<FixedOffset>>) -> Option<DateTime<FixedOffset>> {
+    if let Some(seconds) = record.date_unixtime.as_deref().and_then(|s| s.parse::<i64>().ok()) {
+        return options.time_from_millis(seconds.checked_mul(1000)?);
+    }
+    let naive = NaiveDateTime::parse_from_str(record.date.as_deref()?, "%Y-%m-%dT%H:%M:%S").ok()?;
+    Some(options.time_from_local(naive, previous))
+}
+
+fn message(mut record: Record, source: &Arc<str>, options: &ImportOptions, previous: Option<DateTime<FixedOffset>>) -> Option<Message> {
+    let timestamp = timestamp(&record, options, previous)?;
+    let source = source.clone();
+    match record.kind.as_str() {
+        "message" => {
+            let author = record.from.take().or(record.from_id.take()).unwrap_or_default();
+            let attachment = attachment(&mut record);
+            let edited = record.edited.is_some();
+            let text = record.text.into_string();
+            Some(Message { timestamp, author, text, kind: MessageKind::Text, attachment, edited, source })
+        }
+        "service" => {
+            let author = record.actor.clone().or(record.actor_id.clone()).unwrap_or_default();
+            let (event, text) = service(&record, &author);
+            Some(Message { timestamp, author, text, kind: MessageKind::System(event), attachment: None, edited: false, source })
+        }
+        _ => None,
+    }
+}
+
+/// Read a Telegram export. A whole-account export is read as one chat of all its chats.
+pub fn read(value: Value, options: &ImportOptions, source: &str) -> Result<ImportedChat, Error> {
+    let export: Export = serde_json::from_value(value).map_err(|e| json_error(source, e))?;
+    let (chat_name, records) = match export.chats {
+        Some(chats) => (None, chats.list.into_iter().flat_map(|c| c.messages).collect()),
+        None => (export.name, export.messages),
+    };
+    let mut previous = None;
+    ImportedChat::new(source, chat_name, records, options, |record, source| {
+        let m = message(record, source, options, previous)?;
+        previous = Some(m.timestamp);
+        Some(vec![m])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::ChatSource;
+
+    fn options() -> ImportOptions {
+        ImportOptions { timezone: chrono_tz::UTC, display_timezone: None, strict: false, normalize: true }
+    }
+
+    fn chat(messages: Value) -> Value {
+        serde_json::json!({ "name": "Friends", "messages": messages })
+    }
+
+    #[test]
+    fn formatted_text_is_joined_from_its_parts() {
+        let value = chat(serde_json::json!([{
+            "type": "message",
+            "date": "2021-01-01T12:00:00",
+            "from": "Alice",
+            "text": ["see ", { "type": "link", "text": "https://example.com" }, " and ", { "type": "bold", "text": "hurry" }]
+        }]));
+        let messages: Vec<_> = read(value, &options(), "result.json").unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(messages[0].text, "see https://example.com and hurry");
+    }
+
+    #[test]
+    fn times_out_of_range_are_skipped() {
+        let value = chat(serde_json::json!([
+            { "type": "message", "date_unixtime": "9223372036854776", "from": "Alice", "text": "too late" },
+            { "type": "message", "date_unixtime": "1609502400", "from": "Bob", "text": "hi" }
+        ]));
+        let chat = read(value.clone(), &options(), "result.json").unwrap();
+        assert_eq!(chat.report().skipped_records, 1);
+        let messages: Vec<_> = chat.collect::<Result<_, _>>().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].timestamp.to_rfc3339(), "2021-01-01T12:00:00+00:00");
+
+        let strict = ImportOptions { strict: true, ..options() };
+        assert!(matches!(read(value, &strict, "result.json"), Err(Error::Import(_))));
+    }
+}
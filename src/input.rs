@@ -0,0 +1,144 @@
+use crate::archive::{self, ChatArchive};
+use crate::error::Error;
+use crate::format::{Detection, Detector, FormatArg};
+use crate::parse;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+
+/// How many lines of stdin are held back to detect the format from, as stdin can only be read once
+const STDIN_PREFIX_LINES: usize = 10_000;
+
+/// Where a chat comes from: a text file, an exported zip or stdin (`-`)
+pub struct Input {
+    kind: Kind,
+}
+
+enum Kind {
+    File(String),
+    Zip(String, ChatArchive),
+    Stdin,
+}
+
+fn open_file(path: &str) -> io::Result<File> {
+    File::open(path).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))
+}
+
+fn looks_like_json(start: &[u8]) -> bool {
+    // iOS chats start with `[` too, but followed by a date rather than an object
+    let start = start.strip_prefix("\u{FEFF}".as_bytes()).unwrap_or(start);
+    let mut bytes = start.iter().filter(|b| !b.is_ascii_whitespace());
+    match bytes.next() {
+        Some(b'{') => true,
+        Some(b'[') => matches!(bytes.next(), Some(b'{' | b']') | None),
+        _ => false,
+    }
+}
+
+fn looks_like_xml(start: &[u8]) -> bool {
+    let start = start.strip_prefix("\u{FEFF}".as_bytes()).unwrap_or(start);
+    let start = start.trim_ascii_start();
+    start.starts_with(b"<?xml") || start.starts_with(b"<smses")
+}
+
+impl Input {
+    /// Open a file, telling an exported zip from a text file, or stdin for `-`
+    pub fn open(path: &str) -> Result<Self, Error> {
+        if path == "-" {
+            Ok(Input { kind: Kind::Stdin })
+        } else if archive::is_zip(path) {
+            Ok(Input { kind: Kind::Zip(path.to_string(), ChatArchive::open(open_file(path)?)?) })
+        } else {
+            Ok(Input { kind: Kind::File(path.to_string()) })
+        }
+    }
+
+    /// The name messages from this input are labelled with
+    pub fn name(&self) -> &str {
+        match &self.kind {
+            Kind::File(path) | Kind::Zip(path, _) => path,
+            Kind::Stdin => "<stdin>",
+        }
+    }
+
+    /// The size in bytes of each media file in an exported zip, by file name
+    pub fn media_sizes(&self) -> HashMap<String, u64> {
+        match &self.kind {
+            Kind::Zip(_, archive) => archive.media_sizes.clone(),
+            Kind::File(_) | Kind::Stdin => HashMap::new(),
+        }
+    }
+
+    /// Read the input from the start, as it is
+    pub fn reader(&mut self) -> Result<Box<dyn BufRead + '_>, Error> {
+        match &mut self.kind {
+            Kind::File(path) => Ok(Box::new(BufReader::new(open_file(path)?))),
+            Kind::Zip(_, archive) => Ok(Box::new(BufReader::new(archive.chat()?))),
+            Kind::Stdin => Ok(Box::new(io::stdin().lock())),
+        }
+    }
+
+    fn starts_like(&mut self, extension: &str, looks_like: fn(&[u8]) -> bool) -> Result<bool, Error> {
+        match &self.kind {
+            Kind::Zip(..) => return Ok(false),
+            Kind::File(path) if path.to_lowercase().ends_with(extension) => return Ok(true),
+            Kind::File(_) | Kind::Stdin => {}
+        }
+        // Only peek, so that stdin can still be read from the start
+        let mut reader = self.reader()?;
+        let start = reader.fill_buf()?;
+        Ok(looks_like(start))
+    }
+
+    /// Whether the input is a JSON export from another messenger rather than WhatsApp's text
+    pub fn is_json(&mut self) -> Result<bool, Error> {
+        self.starts_like(".json", looks_like_json)
+    }
+
+    /// Whether the input is a CSV or TSV table, which only the file name tells
+    pub fn is_csv(&self) -> bool {
+        match &self.kind {
+            Kind::File(path) => [".csv", ".tsv"].iter().any(|e| path.to_lowercase().ends_with(e)),
+            Kind::Zip(..) | Kind::Stdin => false,
+        }
+    }
+
+    /// Whether the input is an XML backup of text messages
+    pub fn is_xml(&mut self) -> Result<bool, Error> {
+        self.starts_like(".xml", looks_like_xml)
+    }
+
+    /// Read the whole input as JSON
+    pub fn read_json(&mut self) -> Result<serde_json::Value, Error> {
+        let name = self.name().to_string();
+        serde_json::from_reader(self.reader()?).map_err(|e| Error::Import(format!("{}: {}", name, e)))
+    }
+
+    /// Work out the format of the chat and return a reader from its first line.
+    /// Files are read through once to check every date; stdin can only be read
+    /// once, so the format is detected from its first lines only.
+    pub fn detect(&mut self, arg: FormatArg) -> Result<(Detection, Box<dyn BufRead + '_>), Error> {
+        let mut detector = Detector::new();
+        if let Kind::Stdin = self.kind {
+            let mut stdin = io::stdin().lock();
+            let mut prefix = Vec::new();
+            for _ in 0..STDIN_PREFIX_LINES {
+                let start = prefix.len();
+                if stdin.read_until(b'\n', &mut prefix)? == 0 {
+                    break;
+                }
+                detector.push(&parse::decode_line(&prefix[start..]));
+            }
+            let reader = Cursor::new(prefix).chain(stdin);
+            return Ok((detector.finish(arg), Box::new(reader)));
+        }
+
+        let mut reader = self.reader()?;
+        let mut buf = Vec::new();
+        while let Some(line) = parse::read_line(&mut reader, &mut buf)? {
+            detector.push(&line);
+        }
+        drop(reader);
+        Ok((detector.finish(arg), self.reader()?))
+    }
+}
@@ -0,0 +1,195 @@
+//! Parse WhatsApp chat exports and compute per-user statistics.
+//!
+//! A chat is read one message at a time with a [`MessageReader`], so exports
+//! of any size can be processed in constant memory. Messages are folded into a
+//! [`StatsBuilder`], which yields one [`Stat`] per user:
+//!
+//! ```no_run
+//! use whatsapp_stats::{Input, FormatArg, MessageReader, ParseOptions, StatsBuilder};
+//!
+//! let mut input = Input::open("chat.txt")?;
+//! let (detection, reader) = input.detect(FormatArg::Auto)?;
+//! let mut builder = StatsBuilder::default();
+//! for message in MessageReader::new(reader, ParseOptions::new(detection.format), "chat.txt") {
+//!     builder.add(&message?);
+//! }
+//! for stat in builder.into_stats() {
+//!     println!("{}: {} messages", stat.user, stat.num_messages);
+//! }
+//! # Ok::<(), whatsapp_stats::Error>(())
+//! ```
+//!
+//! Exports from other messengers are read by the importers in [`import`]. Every
+//! kind of export is a [`ChatSource`], a stream of the same [`Message`]s.
+//!
+//! For chats already in memory, [`parse()`] and [`compute_stats`] do the same in one call each.
+//! [`compute_grouped_stats`] counts several exports at once, split into periods, as the
+//! command line tool does.
+
+pub mod alias;
+mod archive;
+mod error;
+mod filter;
+mod format;
+mod group;
+pub mod import;
+mod input;
+mod media;
+pub mod merge;
+mod message;
+mod parse;
+mod source;
+mod stats;
+mod system;
+
+pub use error::{Error, ParseError, ParseErrorKind, ParseReport};
+pub use filter::{DateBound, Filter, TimeUnit};
+pub use format::{Clock, DateOrder, Detection, Format, FormatArg, Layout, Style};
+pub use group::{Bucket, GroupBy};
+pub use input::Input;
+pub use media::{Attachment, AttachmentKind};
+pub use message::{Message, MessageKind};
+pub use parse::{normalize, MessageReader, ParseOptions};
+pub use source::{ChatSource, SourceKind};
+pub use stats::{aggregate, merge_groups, Heatmap, Stat, StatsBuilder};
+pub use system::SystemEvent;
+
+use alias::{Aliases, ExporterGuess};
+use format::Detector;
+use std::collections::BTreeMap;
+
+/// How many messages are handed to the worker threads at a time
+const CHUNK_SIZE: usize = 65_536;
+
+/// Parse a whole chat held in memory, detecting its format and reading its
+/// timestamps as UTC. Lines that can't be parsed are skipped.
+pub fn parse(chat: &str) -> Result<Vec<Message>, Error> {
+    let mut detector = Detector::new();
+    for line in chat.lines() {
+        detector.push(&parse::decode_line(line.as_bytes()));
+    }
+    let format = detector.finish(FormatArg::Auto).format;
+    MessageReader::new(chat.as_bytes(), ParseOptions::new(format), "<chat>").collect()
+}
+
+/// The stats of every user over all of `messages`
+pub fn compute_stats(messages: &[Message]) -> Vec<Stat> {
+    let mut builder = StatsBuilder::default();
+    for m in messages {
+        builder.add(m);
+    }
+    builder.into_stats()
+}
+
+/// Stats over several exports, split into periods
+#[derive(Debug, Default)]
+pub struct GroupedStats {
+    /// The stats of each period, or of the whole chat under `None` when not grouped
+    pub groups: BTreeMap<Option<Bucket>, StatsBuilder>,
+    /// For each source, who "You" was counted as when it was guessed
+    pub exporters: Vec<Option<String>>,
+}
+
+/// The stats of `sources` as the command line tool computes them. Messages are
+/// streamed and counted in parallel chunks on the current rayon thread pool,
+/// and split into periods by `group_by`. Authors are renamed by `aliases`, and
+/// "You" is counted as `me`, or else as the exporter guessed from each source's
+/// system events. The filter comes last, so that it can pick "You" by name.
+pub fn compute_grouped_stats<S: ChatSource>(
+    sources: &mut [S],
+    filter: &Filter,
+    group_by: Option<GroupBy>,
+    me: Option<&str>,
+    aliases: &mut Aliases,
+) -> Result<GroupedStats, Error> {
+    // Messages by "You" are grouped apart, as whether the filter keeps them
+    // depends on who that turns out to be
+    let key = |m: &Message| (m.author == alias::YOU, group_by.map(|g| g.bucket(&m.timestamp)));
+    let keep = |m: &Message| filter.in_range(m.timestamp) && (m.author == alias::YOU || filter.keeps_author(&m.author));
+    let mut stats = GroupedStats::default();
+    let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+
+    for messages in sources {
+        // Each export has its own "You", so stats are kept apart until it's known who that is
+        let mut source_groups = BTreeMap::new();
+        let mut exporter = ExporterGuess::default();
+        loop {
+            let mut message = messages.next().transpose()?;
+            if let Some(m) = &mut message {
+                if let Some(me) = me {
+                    alias::resolve_you(m, me);
+                }
+                aliases.apply(m);
+                exporter.add(m);
+            }
+            let end = message.is_none();
+            // Filtered after the exporter guess, which needs to see every message
+            chunk.extend(message.filter(keep));
+            if chunk.len() == CHUNK_SIZE || end {
+                merge_groups(&mut source_groups, aggregate(&chunk, key));
+                chunk.clear();
+            }
+            if end {
+                break;
+            }
+        }
+        let guess = exporter.guess().filter(|_| me.is_none()).map(str::to_string);
+        let you = guess.as_deref().unwrap_or(alias::YOU);
+        for ((by_you, bucket), mut builder) in source_groups {
+            if by_you {
+                if !filter.keeps_author(you) {
+                    continue;
+                }
+                builder.rename(alias::YOU, you);
+            }
+            stats.groups.entry(bucket).or_default().merge(builder);
+        }
+        stats.exporters.push(guess);
+    }
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(chat: &'static str, name: &str) -> MessageReader<&'static [u8]> {
+        let mut detector = Detector::new();
+        for line in chat.lines() {
+            detector.push(line);
+        }
+        MessageReader::new(chat.as_bytes(), ParseOptions::new(detector.finish(FormatArg::Auto).format), name)
+    }
+
+    fn users(builder: StatsBuilder) -> Vec<(String, u64)> {
+        let mut users: Vec<_> = builder.into_stats().into_iter().map(|s| (s.user, s.num_messages)).collect();
+        users.sort();
+        users
+    }
+
+    #[test]
+    fn grouped_stats_guess_the_exporter_of_each_source() {
+        let mut sources = [
+            source("13/02/2021, 10:00 - You added Bob\n13/02/2021, 10:01 - Sam: hi\n13/02/2021, 10:02 - Bob: hey\n", "a.txt"),
+            source("20/03/2021, 09:00 - Sam: again\n20/03/2021, 09:05 - Alice: hello\n", "b.txt"),
+        ];
+        let filter = Filter { users: vec!["sam".to_string()], ..Filter::default() };
+        let stats = compute_grouped_stats(&mut sources, &filter, Some(GroupBy::Month), None, &mut Aliases::default()).unwrap();
+        assert_eq!(stats.exporters, [Some("Sam".to_string()), None]);
+        let groups: Vec<_> = stats.groups.into_iter().map(|(bucket, builder)| (bucket.unwrap().to_string(), users(builder))).collect();
+        assert_eq!(
+            groups,
+            [("2021-02".to_string(), vec![("Sam".to_string(), 1)]), ("2021-03".to_string(), vec![("Sam".to_string(), 1)])]
+        );
+    }
+
+    #[test]
+    fn grouped_stats_count_you_as_me_when_given() {
+        let mut sources = [source("13/02/2021, 10:00 - You added Bob\n13/02/2021, 10:01 - Sam: hi\n", "a.txt")];
+        let stats = compute_grouped_stats(&mut sources, &Filter::default(), None, Some("Me"), &mut Aliases::default()).unwrap();
+        assert_eq!(stats.exporters, [None]);
+        let builder = stats.groups.into_values().next().unwrap();
+        assert_eq!(builder.system_stats(), [(SystemEvent::Added, 1)]);
+        assert_eq!(users(builder), [("Sam".to_string(), 1)]);
+    }
+}
@@ -1,19 +1,46 @@
-use chrono::{DateTime, FixedOffset, NaiveDateTime, Datelike};
+use chrono::Utc;
+use chrono_tz::Tz;
 use clap::{Parser, ValueEnum};
-use regex::Regex;
-use std::collections::HashMap;
-use std::fs;
+use std::collections::BTreeMap;
+use std::fs::File;
+use serde::Serialize;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use whatsapp_stats::alias::{self, Aliases, AuthorCounts};
+use whatsapp_stats::import::csv::CsvFormat;
+use whatsapp_stats::import::{self, ImportOptions};
+use whatsapp_stats::merge;
+use whatsapp_stats::{AttachmentKind, ChatSource, DateBound, Error, Filter, FormatArg, GroupBy, Heatmap, Input, MessageReader, ParseOptions, SourceKind, Stat, SystemEvent};
 
 #[derive(Parser,Debug)]
 #[command(version, about, long_about= None)]
 struct Args {
-    /// The input file
-    path: String,
+    /// The input files, each either the chat text or the zip made by "Export chat",
+    /// or - for stdin. Stats are combined over all of them
+    #[arg(required = true)]
+    paths: Vec<String>,
 
-    /// Print out per year stats
-    #[arg(short, long, action)]
-    year: bool,
+    /// Print out stats per year, quarter, month, ISO week or day, or per
+    /// weekday or hour of the day over the whole chat
+    #[arg(short, long, value_enum, value_name = "PERIOD")]
+    group_by: Option<GroupBy>,
     
+    /// Only count messages from this date on: 2024-01-31, "2024-01-31 18:00",
+    /// or a time before now like 36h, 90d, 2w, 6m or 1y
+    #[arg(long, value_name = "DATE")]
+    since: Option<DateBound>,
+
+    /// Only count messages before this date, taking in the whole of a date without a time
+    #[arg(long, value_name = "DATE")]
+    until: Option<DateBound>,
+
+    /// Only count messages by this author; can be given several times
+    #[arg(long = "user", value_name = "NAME")]
+    users: Vec<String>,
+
+    /// Leave out messages by this author; can be given several times
+    #[arg(long = "exclude-user", value_name = "NAME")]
+    exclude_users: Vec<String>,
+
     /// Pretty print the table
     #[arg(short, long, action)]
     pretty: bool,
@@ -22,24 +49,96 @@ struct Args {
     #[arg(long, value_enum, default_value_t = SortBy::Messages)]
     sort: SortBy,
 
-}
+    /// Also print when in the week the chat and each user are active, as a
+    /// heatmap of weekdays by hours
+    #[arg(long, action)]
+    heatmap: bool,
 
-#[derive(Debug, Clone)]
-struct Message {
-    timestamp: chrono::DateTime<FixedOffset>,
-    author: String,
-    text: String,
-}
+    /// Print the stats, heatmaps and system event counts as JSON instead
+    #[arg(long, action)]
+    json: bool,
+
+    /// The messenger the inputs were exported from, told from each file by default
+    #[arg(long, value_enum, default_value_t = SourceKind::Auto)]
+    source: SourceKind,
+
+    /// The WhatsApp export format, detected from the file by default
+    #[arg(long, value_enum, default_value_t = FormatArg::Auto)]
+    format: FormatArg,
+
+    /// The IANA timezone the exporting phone was in, e.g. Europe/Berlin
+    #[arg(long, default_value_t = Tz::UTC)]
+    timezone: Tz,
+
+    /// Show times in this IANA timezone instead of the phone's
+    #[arg(long)]
+    display_timezone: Option<Tz>,
+
+    /// Fail on the first line that can't be parsed instead of skipping it
+    #[arg(long, action)]
+    strict: bool,
+
+    /// Also print counts of system events (members added, subject changes, ...)
+    #[arg(long, action)]
+    system_events: bool,
+
+    /// Number of threads to compute stats with, all cores by default
+    #[arg(long, default_value_t = 0)]
+    threads: usize,
 
+    /// Instead of printing stats, merge the inputs into one chat without the messages
+    /// they share and write it to this file, or - for stdout
+    #[arg(long, value_name = "FILE")]
+    merge: Option<String>,
+
+    /// A TOML file mapping the names people appear under to one canonical name each
+    #[arg(long, value_name = "FILE")]
+    aliases: Option<String>,
+
+    /// Your own name, to count messages and events the export shows as "You" under.
+    /// Guessed from system events like "You added Bob" when not given
+    #[arg(long, value_name = "NAME")]
+    me: Option<String>,
+
+    /// Keep invisible marks and no-break spaces in names and text as exported,
+    /// instead of stripping them and normalizing to NFC
+    #[arg(long, action)]
+    keep_invisible: bool,
+
+    /// The column of CSV/TSV inputs holding each message's time, by header or
+    /// by position counting from 1
+    #[arg(long, value_name = "COLUMN", default_value = "timestamp")]
+    timestamp_column: String,
+
+    /// The column of CSV/TSV inputs holding each message's author
+    #[arg(long, value_name = "COLUMN", default_value = "author")]
+    author_column: String,
+
+    /// The column of CSV/TSV inputs holding each message's text
+    #[arg(long, value_name = "COLUMN", default_value = "text")]
+    text_column: String,
+
+    /// How times are written in CSV/TSV inputs, as a chrono format string like
+    /// "%d.%m.%Y %H:%M". RFC 3339, "%Y-%m-%d %H:%M:%S" and Unix seconds are
+    /// recognized by default
+    #[arg(long, value_name = "FORMAT")]
+    time_format: Option<String>,
+
+    /// Only read the conversations of an SMS backup with this contact name
+    /// (or part of it) or phone number
+    #[arg(long, value_name = "SELECTOR")]
+    thread: Option<String>,
+
+    /// Instead of printing stats, list the conversations in SMS backups with how
+    /// many messages each has, to pick one with --thread
+    #[arg(long, action)]
+    list_threads: bool,
+
+    /// Instead of printing stats, list every author name as written in the inputs
+    /// with how many messages it has, to help write an aliases file
+    #[arg(long, action)]
+    list_authors: bool,
 
-#[derive(Debug)]
-struct Stat {
-    user: String, // the user
-    num_messages: u64, // the number of messages the user sent
-    num_words: u64, // the number of words the user sent
-    first_message: DateTime<FixedOffset>, // the date of the first message the user sent
-    percent_messages: f32, // the percentage of all messages that the user sent
-    percent_words: f32 // the percentage of all words that the user sent
 }
 
 #[derive(ValueEnum, Clone, Debug)]
@@ -48,112 +147,104 @@ enum SortBy {
     Words
 }
 
-fn parse(content: &str) -> Vec<Message> {
-    // Parses the file to a vec of messages
-    let re = Regex::new(r"^\[(\d{1,2}/\d{1,2}/\d{2}), (\d{1,2}:\d{2}:\d{2}\u{202F}[AP]M)] (.*?): (.*)").unwrap();
-    let tz_offset = FixedOffset::west_opt(0).unwrap();
-
-    let mut messages = Vec::new();
-    let mut buffer = String::new();
-    let mut current_timestamp = None;
-    let mut current_author = String::new();
-
-    for line in content.lines() {
-        if let Some(caps) = re.captures(line) {
-            if let Some(timestamp) = current_timestamp.take() {
-                messages.push(Message {
-                    timestamp,
-                    author: current_author.clone(),
-                    text: buffer.trim().to_string(),
-                });
-            }
-
-            let datetime_str = format!("{} {}", &caps[1], &caps[2].replace('\u{202F}', " "));
-            let naive = NaiveDateTime::parse_from_str(&datetime_str, "%m/%d/%y %I:%M:%S %p").unwrap();
-            current_timestamp = Some(DateTime::from_naive_utc_and_offset(naive, tz_offset));
-            current_author = caps[3].to_string();
-            buffer = caps[4].to_string();
-        } else {
-            buffer.push('\n');
-            buffer.push_str(line);
-        }
-    }
-
-    if let Some(timestamp) = current_timestamp {
-        messages.push(Message {
-            timestamp,
-            author: current_author,
-            text: buffer.trim().to_string(),
-        });
-    }
-
-    messages
+/// The finished stats of one group of messages, ready to print
+struct Group<K> {
+    key: K,
+    stats: Vec<Stat>,
+    activity: Heatmap,
+    system: Vec<(SystemEvent, u64)>,
 }
 
+fn print_system_stats(counts: Vec<(SystemEvent, u64)>, pretty: bool) {
+    if pretty {
+        use tabled::{Table, Tabled};
 
-fn compute_stats(messages: &[Message]) -> Vec<Stat> {
-    // Given a vec of messages, calculate:
-    // 1. the number of messages each user sent
-    // 2. the number of words each user sent
-    // Optionally, only calculate the statistics for a given year and/or given user
-    // Return a vec of Stat
-        let mut map: HashMap<String, Stat> = HashMap::new();
-    let total_messages = messages.len() as f32;
-    let total_words: f32 = messages.iter().map(|m| m.text.split_whitespace().count() as f32).sum();
+        #[derive(Tabled)]
+        #[allow(non_snake_case)]
+        struct DisplayEvent {
+            Event: String,
+            Count: u64,
+        }
+
+        let display: Vec<DisplayEvent> = counts
+            .into_iter()
+            .map(|(event, count)| DisplayEvent { Event: event.to_string(), Count: count })
+            .collect();
 
-    for m in messages {
-        let entry = map.entry(m.author.clone()).or_insert_with(|| Stat {
-            user: m.author.clone(),
-            num_messages: 0,
-            num_words: 0,
-            first_message: m.timestamp,
-            percent_messages: 0.0,
-            percent_words: 0.0,
-        });
-        entry.num_messages += 1;
-        entry.num_words += m.text.split_whitespace().count() as u64;
-        if m.timestamp < entry.first_message {
-            entry.first_message = m.timestamp;
+        println!("{}", Table::new(display));
+    } else {
+        for (event, count) in counts {
+            println!("{}: {}", event, count);
         }
     }
+}
 
-    for stat in map.values_mut() {
-        stat.percent_messages = stat.num_messages as f32 / total_messages * 100.0;
-        stat.percent_words = stat.num_words as f32 / total_words * 100.0;
+fn media_summary(media: &BTreeMap<AttachmentKind, u64>, unit: &str) -> String {
+    // e.g. `4 media (3 image, 1 sticker)`
+    let total: u64 = media.values().sum();
+    if total == 0 {
+        return format!("0{}", unit);
     }
+    let kinds: Vec<String> = media.iter().map(|(kind, count)| format!("{} {}", count, kind)).collect();
+    format!("{}{} ({})", total, unit, kinds.join(", "))
+}
 
-    map.into_values().collect()
+fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, units[unit])
+    }
 }
 
-fn print_stats(mut stats: Vec<Stat>, pretty: bool, sort: SortBy) {
-    // A function that builds and pretty prints a table of the format:
-    // User | num_messages | num_words | percent_messages | percent_words | first_message
-    // The table should sort the list by either messages or words based on sorting
+fn sort_stats(stats: &mut [Stat], sort: &SortBy) {
+    // Busiest first, by either messages or words
     match sort {
         SortBy::Messages => stats.sort_by_key(|s| std::cmp::Reverse(s.num_messages)),
         SortBy::Words => stats.sort_by_key(|s| std::cmp::Reverse(s.num_words)),
     }
+}
 
+fn print_stats(stats: &[Stat], pretty: bool) {
+    // A function that builds and pretty prints a table of the format:
+    // User | num_messages | num_words | first_message | media | media_bytes | deleted | edited | percent_messages | percent_words
     if pretty {
         use tabled::{Table, Tabled};
 
         #[derive(Tabled)]
+        #[allow(non_snake_case)]
         struct DisplayStat {
             User: String,
             Messages: u64,
             Words: u64,
             First: String,
+            Media: String,
+            #[tabled(rename = "Media size")]
+            Media_size: String,
+            Deleted: u64,
+            Edited: u64,
             percent_messages: String,
             percent_words: String,
         }
 
         let display: Vec<DisplayStat> = stats
-            .into_iter()
+            .iter()
             .map(|s| DisplayStat {
-                User: s.user,
+                User: s.user.clone(),
                 Messages: s.num_messages,
                 Words: s.num_words,
                 First: s.first_message.format("%Y-%m-%d %H:%M:%S").to_string(),
+                Media: media_summary(&s.media, ""),
+                Media_size: format_bytes(s.media_bytes),
+                Deleted: s.num_deleted,
+                Edited: s.num_edited,
                 percent_messages: format!("{:.2}%", s.percent_messages),
                 percent_words: format!("{:.2}%", s.percent_words),
             })
@@ -164,11 +255,15 @@ fn print_stats(mut stats: Vec<Stat>, pretty: bool, sort: SortBy) {
     } else {
         for s in stats {
             println!(
-                "{}: {} msgs, {} words, first at {}, {:.2}% msgs, {:.2}% words",
+                "{}: {} msgs, {} words, first at {}, {}, {} of media, {} deleted, {} edited, {:.2}% msgs, {:.2}% words",
                 s.user,
                 s.num_messages,
                 s.num_words,
                 s.first_message,
+                media_summary(&s.media, " media"),
+                format_bytes(s.media_bytes),
+                s.num_deleted,
+                s.num_edited,
                 s.percent_messages,
                 s.percent_words
             );
@@ -178,23 +273,270 @@ fn print_stats(mut stats: Vec<Stat>, pretty: bool, sort: SortBy) {
 
 
 
+fn print_heatmap(title: &str, heatmap: &Heatmap, color: bool) {
+    // A row per weekday and two characters per hour, shaded by how busy the hour
+    // is compared to the busiest one: in green on terminals, in blocks otherwise
+    const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+    const SHADES: [&str; 5] = [" ·", "░░", "▒▒", "▓▓", "██"];
+    const COLORS: [u8; 5] = [236, 22, 28, 34, 46]; // from the 256-color palette
+    let max = heatmap.max();
+    println!("\n{} (busiest hour: {} msgs)", title, max);
+    let hours: String = (0..24).step_by(3).map(|hour| format!("{:<6}", format!("{:02}", hour))).collect();
+    println!("    {}", hours.trim_end());
+    for (day, row) in DAYS.iter().zip(heatmap.rows()) {
+        let cells: String = row
+            .iter()
+            .map(|&count| {
+                let level = if count == 0 { 0 } else { (count * 4).div_ceil(max) as usize };
+                if color {
+                    format!("\x1b[48;5;{}m  \x1b[0m", COLORS[level])
+                } else {
+                    SHADES[level].to_string()
+                }
+            })
+            .collect();
+        println!("{} {}", day, cells);
+    }
+}
+
+fn print_json<K: Serialize>(groups: Vec<Group<K>>) -> Result<(), Error> {
+    // One object per group, with the same stats as the tables plus the heatmaps
+    let groups: Vec<serde_json::Value> = groups
+        .into_iter()
+        .map(|g| {
+            let system: serde_json::Map<String, serde_json::Value> =
+                g.system.into_iter().map(|(event, count)| (event.to_string(), count.into())).collect();
+            serde_json::json!({ "group": g.key, "activity": g.activity, "users": g.stats, "system_events": system })
+        })
+        .collect();
+    let mut out = io::stdout().lock();
+    serde_json::to_writer(&mut out, &groups).map_err(io::Error::from)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn open_source<'a>(input: &'a mut Input, args: &Args) -> Result<Box<dyn ChatSource + 'a>, Error> {
+    // Works out what an input is, saying what was guessed, and starts reading its messages
+    let media_sizes = input.media_sizes();
+    let name = input.name().to_string();
+    let kind = match args.source {
+        SourceKind::Auto if input.is_json()? => None,
+        SourceKind::Auto if input.is_xml()? => Some(SourceKind::Sms),
+        SourceKind::Auto if input.is_csv() => Some(SourceKind::Csv),
+        SourceKind::Auto => Some(SourceKind::Whatsapp),
+        kind => Some(kind),
+    };
+    let options = ImportOptions {
+        timezone: args.timezone,
+        display_timezone: args.display_timezone,
+        strict: args.strict,
+        normalize: !args.keep_invisible,
+    };
+    match kind {
+        None => return Ok(Box::new(import::read_json(input.read_json()?, &options, &name)?)),
+        Some(SourceKind::Telegram) => return Ok(Box::new(import::telegram::read(input.read_json()?, &options, &name)?)),
+        Some(SourceKind::Signal) => return Ok(Box::new(import::signal::read(input.read_json()?, &options, &name)?)),
+        Some(SourceKind::Messenger) => return Ok(Box::new(import::messenger::read(input.read_json()?, &options, &name)?)),
+        Some(SourceKind::Sms) => {
+            let chat = import::sms::read(input.reader()?, &options, &name, args.thread.as_deref())?;
+            return Ok(Box::new(chat));
+        }
+        Some(SourceKind::Csv) => {
+            let format = CsvFormat {
+                timestamp: args.timestamp_column.clone(),
+                author: args.author_column.clone(),
+                text: args.text_column.clone(),
+                time_format: args.time_format.clone(),
+            };
+            return Ok(Box::new(import::csv::read(input.reader()?, &format, &options, &name)?));
+        }
+        Some(_) => {}
+    }
+
+    let (detection, reader) = input.detect(args.format)?;
+    if !detection.pinned {
+        eprintln!(
+            "Reading dates in {} as {} (confidence {:.0}%)",
+            name,
+            detection.format.date_order,
+            detection.date_order_confidence * 100.0
+        );
+    }
+
+    let options = ParseOptions {
+        format: detection.format,
+        timezone: args.timezone,
+        display_timezone: args.display_timezone,
+        strict: args.strict,
+        media_sizes,
+        normalize: !args.keep_invisible,
+    };
+    Ok(Box::new(MessageReader::new(reader, options, &name)))
+}
+
+fn warn_unclean(messages: &dyn ChatSource) {
+    if !messages.report().is_empty() {
+        eprintln!("Warning: some lines in {} could not be read cleanly: {}", messages.source(), messages.report());
+    }
+}
+
+fn merge_inputs(inputs: &mut [Input], args: &Args, out: &str, aliases: &mut Aliases) -> Result<(), Error> {
+    // The merged chat is written in the format of the first WhatsApp input
+    let mut readers = Vec::new();
+    for input in inputs.iter_mut() {
+        readers.push(open_source(input, args)?);
+    }
+    let format = readers.iter().find_map(|r| r.format()).unwrap_or_default();
+    let out: Box<dyn Write> = if out == "-" {
+        Box::new(BufWriter::new(io::stdout().lock()))
+    } else {
+        let file = File::create(out).map_err(|e| io::Error::new(e.kind(), format!("{}: {}", out, e)))?;
+        Box::new(BufWriter::new(file))
+    };
+
+    let report = merge::merge(&mut readers, format, out, args.me.as_deref(), aliases)?;
+    for messages in &readers {
+        warn_unclean(messages.as_ref());
+    }
+    eprintln!("Merged {} messages, dropped {} duplicates", report.written, report.duplicates);
+    Ok(())
+}
+
+fn list_authors(inputs: &mut [Input], args: &Args, aliases: &mut Aliases) -> Result<(), Error> {
+    // Raw names are listed before aliases are applied, with where an alias sends them
+    let mut counts = AuthorCounts::default();
+    for input in inputs.iter_mut() {
+        let mut messages = open_source(input, args)?;
+        for message in &mut messages {
+            counts.add(&message?);
+        }
+        warn_unclean(messages.as_ref());
+    }
+    for (author, count) in counts.into_sorted() {
+        match aliases.resolve(&author) {
+            Some(canonical) => println!("{:>8}  {} -> {}", count, author, canonical),
+            None => println!("{:>8}  {}", count, author),
+        }
+    }
+    Ok(())
+}
+
+fn list_threads(inputs: &mut [Input]) -> Result<(), Error> {
+    // Headed by the file when there are several backups
+    let several = inputs.len() > 1;
+    for input in inputs.iter_mut() {
+        let name = input.name().to_string();
+        if several {
+            println!("{}:", name);
+        }
+        for thread in import::sms::threads(input.reader()?, &name)? {
+            println!("{:>8}  {} ({})", thread.num_messages, thread.name, thread.numbers.join(", "));
+        }
+    }
+    Ok(())
+}
+
+fn filter(args: &Args) -> Result<Filter, Error> {
+    // Dates are in the timezone messages are shown in, so that days start at their midnight
+    let timezone = args.display_timezone.unwrap_or(args.timezone);
+    let now = Utc::now();
+    let resolve = |bound: Option<DateBound>, end: bool| {
+        bound
+            .map(|b| b.resolve(timezone, now, end).ok_or_else(|| io::Error::other(format!("{:?} is out of range", b))))
+            .transpose()
+    };
+    Ok(Filter {
+        since: resolve(args.since, false)?,
+        until: resolve(args.until, true)?,
+        users: args.users.clone(),
+        exclude_users: args.exclude_users.clone(),
+    })
+}
+
 fn main() {
     let args = Args::parse();
-    let content = fs::read_to_string(&args.path).expect("Failed to read file");
-    let messages = parse(&content);
-    if args.year {
-        let mut grouped: HashMap<i32, Vec<Message>> = HashMap::new();
-        for msg in &messages {
-            grouped.entry(msg.timestamp.year()).or_default().push(msg.clone());
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        if let Error::Parse(e) = e {
+            eprintln!("{:>6} | {}", e.line, e.text);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Error> {
+    if args.paths.iter().filter(|p| *p == "-").count() > 1 {
+        return Err(std::io::Error::other("stdin (-) can only be read once").into());
+    }
+    let mut inputs = args.paths.iter().map(|p| Input::open(p)).collect::<Result<Vec<_>, _>>()?;
+
+    let mut aliases = match &args.aliases {
+        Some(path) => Aliases::load(path)?,
+        None => Aliases::default(),
+    };
+    if args.list_threads {
+        return list_threads(&mut inputs);
+    }
+    if args.list_authors {
+        return list_authors(&mut inputs, &args, &mut aliases);
+    }
+    if let Some(out) = &args.merge {
+        return merge_inputs(&mut inputs, &args, out, &mut aliases);
+    }
+
+    rayon::ThreadPoolBuilder::new().num_threads(args.threads).build_global().map_err(io::Error::other)?;
+    let filter = filter(&args)?;
+    let mut sources = Vec::new();
+    for input in inputs.iter_mut() {
+        sources.push(open_source(input, &args)?);
+    }
+    let stats = whatsapp_stats::compute_grouped_stats(&mut sources, &filter, args.group_by, args.me.as_deref(), &mut aliases)?;
+    for (messages, exporter) in sources.iter().zip(&stats.exporters) {
+        warn_unclean(messages.as_ref());
+        if let Some(me) = exporter {
+            eprintln!("Counting \"{}\" in {} as {}", alias::YOU, messages.source(), me);
         }
+    }
+
+    let groups: Vec<_> = stats
+        .groups
+        .into_iter()
+        .map(|(key, builder)| {
+            let system = builder.system_stats();
+            let activity = *builder.activity();
+            let mut stats = builder.into_stats();
+            sort_stats(&mut stats, &args.sort);
+            Group { key, stats, activity, system }
+        })
+        .collect();
+    if args.json {
+        return print_json(groups);
+    }
 
-        for (year, msgs) in grouped.into_iter().collect::<std::collections::BTreeMap<_,_>>() {
-            println!("\n=== Stats for {} ===", year);
-            let stats = compute_stats(&msgs);
-            print_stats(stats, args.pretty, args.sort.clone());
+    let color = io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    for Group { key, stats, activity, system } in groups {
+        // A period with only system events has nothing to show but them
+        if stats.is_empty() && !args.system_events {
+            continue;
         }
-    } else {
-        let stats = compute_stats(&messages);
-        print_stats(stats, args.pretty, args.sort);
+        let title = key.map_or(String::new(), |bucket| format!(" for {}", bucket));
+        if !stats.is_empty() {
+            if key.is_some() {
+                println!("\n=== Stats{} ===", title);
+            }
+            print_stats(&stats, args.pretty);
+        }
+        if args.heatmap && !stats.is_empty() {
+            println!("\n=== Activity{} ===", title);
+            print_heatmap("Everyone", &activity, color);
+            for s in &stats {
+                print_heatmap(&s.user, &s.activity, color);
+            }
+        }
+        if args.system_events {
+            println!("\n=== System events{} ===", title);
+            print_system_stats(system, args.pretty);
         }
+    }
+    Ok(())
 }
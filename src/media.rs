@@ -0,0 +1,180 @@
+use crate::format::Layout;
+use regex::Regex;
+use serde::Serialize;
+use std::fmt;
+
+/// What kind of media an attachment is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentKind {
+    /// A photo or picture
+    Image,
+    /// A video
+    Video,
+    /// An audio file
+    Audio,
+    /// A voice message recorded in the app
+    VoiceNote,
+    /// A sticker
+    Sticker,
+    /// An animated GIF
+    Gif,
+    /// Any other file
+    Document,
+    /// A shared contact card
+    Contact,
+    /// Media of an unknown kind, as Android's `<Media omitted>` doesn't say what it was
+    Unknown,
+}
+
+impl fmt::Display for AttachmentKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            AttachmentKind::Image => "image",
+            AttachmentKind::Video => "video",
+            AttachmentKind::Audio => "audio",
+            AttachmentKind::VoiceNote => "voice note",
+            AttachmentKind::Sticker => "sticker",
+            AttachmentKind::Gif => "GIF",
+            AttachmentKind::Document => "document",
+            AttachmentKind::Contact => "contact",
+            AttachmentKind::Unknown => "media",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Media sent with a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    /// What kind of media it is
+    pub kind: AttachmentKind,
+    /// The file name, when the export included the media
+    pub file: Option<String>,
+    /// The size in bytes, when the file is in the exported zip
+    pub size: Option<u64>,
+}
+
+impl AttachmentKind {
+    fn from_omitted(word: &str) -> Self {
+        match word.to_lowercase().as_str() {
+            "image" => AttachmentKind::Image,
+            "video" => AttachmentKind::Video,
+            "audio" => AttachmentKind::Audio,
+            "sticker" => AttachmentKind::Sticker,
+            "gif" => AttachmentKind::Gif,
+            "document" => AttachmentKind::Document,
+            "contact card" => AttachmentKind::Contact,
+            _ => AttachmentKind::Unknown,
+        }
+    }
+
+    /// The kind of a file of this MIME type, e.g. `image/jpeg`
+    pub fn from_content_type(content_type: &str) -> Self {
+        match content_type.split_once('/').map_or(content_type, |(t, _)| t) {
+            _ if content_type == "image/gif" => AttachmentKind::Gif,
+            "image" => AttachmentKind::Image,
+            "video" => AttachmentKind::Video,
+            "audio" => AttachmentKind::Audio,
+            _ if content_type.contains("vcard") => AttachmentKind::Contact,
+            _ => AttachmentKind::Document,
+        }
+    }
+
+    /// Guess the kind from the names WhatsApp gives attached files, e.g.
+    /// `IMG-20210101-WA0001.jpg` or `00000012-PHOTO-2021-01-01-12-00-00.jpg`
+    pub fn from_file_name(file: &str) -> Self {
+        let upper = file.to_uppercase();
+        let extension = upper.rsplit_once('.').map_or("", |(_, e)| e);
+        if upper.starts_with("STK-") || upper.contains("-STICKER-") {
+            AttachmentKind::Sticker
+        } else if upper.starts_with("PTT-") || (upper.contains("-AUDIO-") && extension == "OPUS") {
+            AttachmentKind::VoiceNote
+        } else if upper.contains("-GIF-") || extension == "GIF" {
+            AttachmentKind::Gif
+        } else if upper.starts_with("IMG-") || upper.contains("-PHOTO-") {
+            AttachmentKind::Image
+        } else if upper.starts_with("VID-") || upper.contains("-VIDEO-") {
+            AttachmentKind::Video
+        } else if upper.starts_with("AUD-") || upper.contains("-AUDIO-") {
+            AttachmentKind::Audio
+        } else {
+            match extension {
+                "JPG" | "JPEG" | "PNG" | "HEIC" | "WEBP" => AttachmentKind::Image,
+                "MP4" | "MOV" | "3GP" | "MKV" => AttachmentKind::Video,
+                "OPUS" => AttachmentKind::VoiceNote,
+                "MP3" | "M4A" | "AAC" | "OGG" | "WAV" => AttachmentKind::Audio,
+                "VCF" => AttachmentKind::Contact,
+                _ => AttachmentKind::Document,
+            }
+        }
+    }
+}
+
+impl Attachment {
+    /// The placeholder a phone with this layout writes for the attachment
+    pub fn placeholder(&self, layout: Layout) -> String {
+        match (layout, &self.file) {
+            (Layout::Android, Some(file)) => format!("{} (file attached)", file),
+            (Layout::Android, None) => "<Media omitted>".to_string(),
+            (Layout::Ios, None) if self.kind == AttachmentKind::Unknown => "<Media omitted>".to_string(),
+            (Layout::Ios, Some(file)) => format!("<attached: {}>", file),
+            (Layout::Ios, None) => match self.kind {
+                AttachmentKind::VoiceNote => "audio omitted".to_string(),
+                AttachmentKind::Contact => "Contact card omitted".to_string(),
+                kind => format!("{} omitted", kind),
+            },
+        }
+    }
+}
+
+pub(crate) struct MediaMatcher {
+    omitted: Regex,
+    document: Regex,
+    attached: Regex,
+    file_attached: Regex,
+}
+
+impl Default for MediaMatcher {
+    fn default() -> Self {
+        MediaMatcher::new()
+    }
+}
+
+impl MediaMatcher {
+    pub fn new() -> Self {
+        MediaMatcher {
+            // Android without media: `<Media omitted>`, iOS: `image omitted`
+            omitted: Regex::new(r"^(?:<Media omitted>|(?i)(image|video|audio|sticker|GIF|contact card) omitted)$").unwrap(),
+            // iOS: `Report.pdf • 3 pages ‎document omitted`
+            document: Regex::new(r"^(?:(?P<file>.+?)(?: • .*)? )?\u{200E}?document omitted$").unwrap(),
+            // iOS with media: `<attached: 00000012-PHOTO-2021-01-01-12-00-00.jpg>`
+            attached: Regex::new(r"^<attached: (?P<file>[^>]+)>").unwrap(),
+            // Android with media: `IMG-20210101-WA0001.jpg (file attached)`
+            file_attached: Regex::new(r"^(?P<file>.+?) \(file attached\)").unwrap(),
+        }
+    }
+
+    /// The attachment a message carries and the rest of its text (the caption),
+    /// or None if it's plain text
+    pub fn attachment(&self, text: &str) -> Option<(Attachment, String)> {
+        let text = text.trim_start_matches('\u{200E}');
+        // The placeholder is on the first line, a caption may follow it
+        let (first, caption) = text.split_once('\n').unwrap_or((text, ""));
+        let first = first.trim_end();
+
+        let attachment = if let Some(caps) = self.omitted.captures(first) {
+            let kind = caps.get(1).map_or(AttachmentKind::Unknown, |m| AttachmentKind::from_omitted(m.as_str()));
+            Attachment { kind, file: None, size: None }
+        } else if let Some(caps) = self.document.captures(first) {
+            let file = caps.name("file").map(|m| m.as_str().to_string());
+            Attachment { kind: AttachmentKind::Document, file, size: None }
+        } else if let Some(caps) = self.attached.captures(first).or_else(|| self.file_attached.captures(first)) {
+            let file = caps["file"].to_string();
+            Attachment { kind: AttachmentKind::from_file_name(&file), file: Some(file), size: None }
+        } else {
+            return None;
+        };
+        Some((attachment, caption.trim().to_string()))
+    }
+}
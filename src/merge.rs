@@ -0,0 +1,254 @@
+//! Merging overlapping exports of the same chat into one, written back as a
+//! WhatsApp chat without the messages they share.
+
+use crate::alias::{self, Aliases};
+use crate::error::Error;
+use crate::format::{Format, Layout};
+use crate::message::{Message, MessageKind};
+use crate::source::ChatSource;
+use chrono::Timelike;
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// How far apart, in minutes, two exports may place the same message. Phones
+/// stamp messages with their own clock, so they can disagree by a minute.
+const WINDOW_MINUTES: i64 = 1;
+
+/// A message waiting to be written, with the inputs it has been seen in
+struct Entry {
+    message: Message,
+    inputs: Vec<usize>,
+}
+
+/// What merging did
+#[derive(Debug, Default)]
+pub struct MergeReport {
+    /// The number of messages in the merged chat
+    pub written: u64,
+    /// The number of messages dropped because an earlier input had them
+    pub duplicates: u64,
+}
+
+fn minute(m: &Message) -> i64 {
+    m.timestamp.timestamp().div_euclid(60)
+}
+
+fn same_message(a: &Message, b: &Message) -> bool {
+    // Authors are left out: each phone names people after its own contacts
+    if a.kind != b.kind || (minute(a) - minute(b)).abs() > WINDOW_MINUTES {
+        return false;
+    }
+    match a.kind {
+        MessageKind::Deleted => true,
+        // Drop the actor, which is "You" on one phone and a name on the other
+        MessageKind::System(_) => {
+            let a_text = a.text.strip_prefix(a.author.as_str()).unwrap_or(&a.text);
+            let b_text = b.text.strip_prefix(b.author.as_str()).unwrap_or(&b.text);
+            a_text.trim() == b_text.trim()
+        }
+        // An attachment is only kept when the export had the file, so only compare captions
+        MessageKind::Text => a.attachment.is_some() == b.attachment.is_some() && a.text.trim() == b.text.trim(),
+    }
+}
+
+fn author_rank(author: &str) -> u8 {
+    // A contact name says more than a phone number, which says more than "You"
+    if author.is_empty() || author == "You" {
+        0
+    } else if author.chars().all(|c| c.is_ascii_digit() || " +-()\u{202A}\u{202C}\u{00A0}".contains(c)) {
+        1
+    } else {
+        2
+    }
+}
+
+fn absorb(kept: &mut Message, other: Message) {
+    // Fills in what one export knows and the other doesn't
+    if kept.timestamp.second() == 0 && other.timestamp.second() != 0 && minute(kept) == minute(&other) {
+        kept.timestamp = other.timestamp;
+    }
+    if author_rank(&other.author) > author_rank(&kept.author) {
+        kept.author = other.author;
+    }
+    if let (Some(a), Some(b)) = (&mut kept.attachment, other.attachment) {
+        if a.file.is_none() || a.size.is_none() && b.size.is_some() {
+            *a = b;
+        }
+    }
+    kept.edited |= other.edited;
+}
+
+/// Writes messages back out as a chat export in one format
+struct Writer<W> {
+    out: W,
+    format: Format,
+}
+
+impl<W: Write> Writer<W> {
+    fn write(&mut self, m: &Message, chat_name: &str) -> std::io::Result<()> {
+        let layout = self.format.layout;
+        let header = self.format.render_header(m.timestamp.naive_local());
+        // iOS marks lines WhatsApp wrote itself with a left-to-right mark
+        let marked = layout == Layout::Ios && (m.attachment.is_some() || m.kind != MessageKind::Text);
+        let mark = if marked { "\u{200E}" } else { "" };
+        let body = match m.kind {
+            MessageKind::System(_) => match layout {
+                Layout::Android => m.text.clone(),
+                Layout::Ios => format!("{}: \u{200E}{}", chat_name, m.text),
+            },
+            MessageKind::Deleted => format!("{}: {}This message was deleted", m.author, mark),
+            MessageKind::Text => {
+                let mut text = match &m.attachment {
+                    Some(a) if m.text.is_empty() => a.placeholder(layout),
+                    Some(a) => format!("{}\n{}", a.placeholder(layout), m.text),
+                    None => m.text.clone(),
+                };
+                if m.edited {
+                    text.push_str(if layout == Layout::Ios { " \u{200E}<This message was edited>" } else { " <This message was edited>" });
+                }
+                format!("{}: {}{}", m.author, mark, text)
+            }
+        };
+        writeln!(self.out, "{}{}{}", mark, header, body)
+    }
+}
+
+/// Merge the messages of several exports of the same chat into one, in time
+/// order and with the messages found in more than one export written once.
+/// The merged chat is written in `format`, with "You" written as `me` when
+/// given and authors renamed by `aliases`.
+pub fn merge<S: ChatSource, W: Write>(
+    readers: &mut [S],
+    format: Format,
+    out: W,
+    me: Option<&str>,
+    aliases: &mut Aliases,
+) -> Result<MergeReport, Error> {
+    let mut next_message = |reader: &mut S| -> Result<Option<Message>, Error> {
+        let mut message = reader.next().transpose()?;
+        if let Some(m) = &mut message {
+            if let Some(me) = me {
+                alias::resolve_you(m, me);
+            }
+            aliases.apply(m);
+        }
+        Ok(message)
+    };
+    let mut heads = Vec::with_capacity(readers.len());
+    for reader in readers.iter_mut() {
+        heads.push(next_message(reader)?);
+    }
+    let mut writer = Writer { out, format };
+    let mut window: VecDeque<Entry> = VecDeque::new();
+    let mut report = MergeReport::default();
+
+    loop {
+        // Take the earliest message any input has left, the first input winning ties
+        let next = heads
+            .iter()
+            .enumerate()
+            .filter_map(|(i, m)| m.as_ref().map(|m| (m.timestamp, i)))
+            .min()
+            .map(|(_, i)| i);
+
+        // Messages too old to have a duplicate coming can be written out
+        let cutoff = next.and_then(|i| heads[i].as_ref()).map(minute);
+        while let Some(front) = window.front() {
+            if cutoff.is_some_and(|c| c - minute(&front.message) <= WINDOW_MINUTES) {
+                break;
+            }
+            let entry = window.pop_front().unwrap();
+            let chat_name = readers.iter().find_map(|r| r.chat_name()).unwrap_or("WhatsApp");
+            writer.write(&entry.message, chat_name)?;
+            report.written += 1;
+        }
+
+        let Some(i) = next else { break };
+        let message = std::mem::replace(&mut heads[i], next_message(&mut readers[i])?).unwrap();
+        let duplicate = window
+            .iter_mut()
+            .find(|e| !e.inputs.contains(&i) && same_message(&e.message, &message));
+        match duplicate {
+            Some(entry) => {
+                entry.inputs.push(i);
+                absorb(&mut entry.message, message);
+                report.duplicates += 1;
+            }
+            None => window.push_back(Entry { message, inputs: vec![i] }),
+        }
+    }
+    writer.out.flush()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{MessageReader, ParseOptions};
+    use crate::system::SystemEvent;
+    use chrono::DateTime;
+
+    fn message(time: &str, author: &str, text: &str, kind: MessageKind) -> Message {
+        Message {
+            timestamp: DateTime::parse_from_rfc3339(&format!("2021-03-01T{}+00:00", time)).unwrap(),
+            author: author.to_string(),
+            text: text.to_string(),
+            kind,
+            attachment: None,
+            edited: false,
+            source: "test".into(),
+        }
+    }
+
+    #[test]
+    fn the_same_event_matches_whoever_caused_it() {
+        let added = MessageKind::System(SystemEvent::Added);
+        let a = message("10:00:00", "You", "You added Bob", added);
+        let b = message("10:00:00", "Sam", "Sam added Bob", added);
+        assert!(same_message(&a, &b));
+        let c = message("10:00:00", "Sam", "Sam added Alice", added);
+        assert!(!same_message(&a, &c));
+    }
+
+    #[test]
+    fn messages_match_within_the_window() {
+        let a = message("10:00:30", "Sam", "hi", MessageKind::Text);
+        assert!(same_message(&a, &message("10:01:59", "+1 555 0100", "hi ", MessageKind::Text)));
+        assert!(!same_message(&a, &message("10:02:00", "Sam", "hi", MessageKind::Text)));
+        assert!(!same_message(&a, &message("10:00:30", "Sam", "hi", MessageKind::Deleted)));
+    }
+
+    #[test]
+    fn absorb_keeps_the_most_telling_author_and_the_seconds() {
+        let mut kept = message("10:00:00", "You", "hi", MessageKind::Text);
+        absorb(&mut kept, message("10:00:42", "+1 555 0100", "hi", MessageKind::Text));
+        assert_eq!(kept.author, "+1 555 0100");
+        assert_eq!(kept.timestamp.second(), 42);
+        absorb(&mut kept, message("10:00:00", "Sam", "hi", MessageKind::Text));
+        assert_eq!(kept.author, "Sam");
+        assert_eq!(kept.timestamp.second(), 42);
+        absorb(&mut kept, message("10:00:00", "You", "hi", MessageKind::Text));
+        assert_eq!(kept.author, "Sam");
+    }
+
+    #[test]
+    fn overlapping_exports_are_written_once() {
+        // Sam's phone and Bob's, whose clock runs a minute ahead and who has Sam as a number
+        let sams = "01/03/2021, 10:00 - You added Bob\n01/03/2021, 10:01 - Sam: hi Bob\n01/03/2021, 10:05 - Bob: hey\n";
+        let bobs = "01/03/2021, 10:02 - +1 555 0100: hi Bob\n01/03/2021, 10:06 - You: hey\n01/03/2021, 10:07 - You: later\n";
+        let mut readers = [
+            MessageReader::new(sams.as_bytes(), ParseOptions::new(Format::default()), "sam.txt"),
+            MessageReader::new(bobs.as_bytes(), ParseOptions::new(Format::default()), "bob.txt"),
+        ];
+        let mut out = Vec::new();
+        let report = merge(&mut readers, Format::default(), &mut out, None, &mut Aliases::default()).unwrap();
+        assert_eq!((report.written, report.duplicates), (4, 2));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "01/03/2021, 10:00 - You added Bob\n\
+             01/03/2021, 10:01 - Sam: hi Bob\n\
+             01/03/2021, 10:05 - Bob: hey\n\
+             01/03/2021, 10:07 - You: later\n"
+        );
+    }
+}
@@ -0,0 +1,35 @@
+use crate::media::Attachment;
+use crate::system::SystemEvent;
+use chrono::{DateTime, FixedOffset};
+use std::sync::Arc;
+
+/// One message of a chat, as read from an export
+#[derive(Debug, Clone)]
+pub struct Message {
+    /// When the message was sent, in the timezone it was read in
+    pub timestamp: DateTime<FixedOffset>,
+    /// Who wrote it; for system events, whoever caused it if anyone
+    pub author: String,
+    /// What was written; for attachments, only the caption
+    pub text: String,
+    /// Whether it is a message, a deleted one or a system event
+    pub kind: MessageKind,
+    /// The media sent with the message, if any. Importers keep the first when an
+    /// export lists several for one message.
+    pub attachment: Option<Attachment>,
+    /// Whether the message was edited after it was sent
+    pub edited: bool,
+    /// The input the message was read from
+    pub source: Arc<str>,
+}
+
+/// What a message is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    /// Something a user wrote
+    Text,
+    /// Something a user wrote and then deleted
+    Deleted,
+    /// A line WhatsApp wrote into the chat
+    System(SystemEvent),
+}
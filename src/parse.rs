@@ -0,0 +1,468 @@
+use crate::error::{Error, ParseError, ParseErrorKind, ParseReport};
+use crate::format::{Format, Layout, LineMatcher};
+use crate::media::MediaMatcher;
+use crate::message::{Message, MessageKind};
+use crate::system::{SystemEvent, SystemMatcher};
+use chrono::{DateTime, FixedOffset, LocalResult, NaiveDateTime, Offset, TimeDelta, TimeZone};
+use chrono_tz::Tz;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::sync::Arc;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+/// How a chat is read
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// The export format, usually from [`Input::detect`](crate::Input::detect)
+    pub format: Format,
+    /// The timezone the timestamps in the file are written in
+    pub timezone: Tz,
+    /// The timezone to convert timestamps to
+    pub display_timezone: Option<Tz>,
+    /// Fail on bad lines rather than skipping them
+    pub strict: bool,
+    /// Sizes of the attached files, by file name
+    pub media_sizes: HashMap<String, u64>,
+    /// Strip invisible marks from authors and text and normalize them to NFC
+    pub normalize: bool,
+}
+
+impl ParseOptions {
+    /// Read `format` as UTC, skipping bad lines and normalizing text
+    pub fn new(format: Format) -> Self {
+        ParseOptions {
+            format,
+            timezone: Tz::UTC,
+            display_timezone: None,
+            strict: false,
+            media_sizes: HashMap::new(),
+            normalize: true,
+        }
+    }
+}
+
+/// Decode a raw line, dropping the line ending and any byte order mark and
+/// replacing invalid UTF-8. Valid lines are borrowed, replaced ones owned.
+pub(crate) fn decode_line(line: &[u8]) -> Cow<'_, str> {
+    let mut line = line.strip_prefix("\u{FEFF}".as_bytes()).unwrap_or(line);
+    while let [rest @ .., b'\n' | b'\r'] = line {
+        line = rest;
+    }
+    String::from_utf8_lossy(line)
+}
+
+/// Read one line into `buf` and decode it, returning None at the end of the input
+pub(crate) fn read_line<'a, R: BufRead>(reader: &mut R, buf: &'a mut Vec<u8>) -> std::io::Result<Option<Cow<'a, str>>> {
+    buf.clear();
+    if reader.read_until(b'\n', buf)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(decode_line(buf)))
+}
+
+fn is_invisible(c: char) -> bool {
+    // Bidi marks, embeddings, overrides and isolates, and byte order marks
+    matches!(c, '\u{200E}' | '\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' | '\u{FEFF}')
+}
+
+/// Strip the invisible marks phones put around names and phone numbers, turn
+/// no-break spaces into plain ones and normalize to NFC, so that the same name
+/// is always the same string
+pub fn normalize(text: &str) -> Cow<'_, str> {
+    if text.is_ascii() {
+        return Cow::Borrowed(text);
+    }
+    let needs_cleaning = text.chars().any(|c| is_invisible(c) || c == '\u{00A0}' || c == '\u{202F}');
+    if !needs_cleaning && is_nfc_quick(text.chars()) == IsNormalized::Yes {
+        return Cow::Borrowed(text);
+    }
+    let cleaned = text
+        .chars()
+        .filter(|&c| !is_invisible(c))
+        .map(|c| if c == '\u{00A0}' || c == '\u{202F}' { ' ' } else { c })
+        .nfc()
+        .collect::<String>();
+    Cow::Owned(cleaned)
+}
+
+pub(crate) fn normalize_owned(text: String) -> String {
+    // Only allocates when something had to change
+    match normalize(&text) {
+        Cow::Borrowed(t) if t.trim().len() == t.len() => None,
+        cleaned => Some(cleaned.trim().to_string()),
+    }
+    .unwrap_or(text)
+}
+
+pub(crate) fn localize(naive: NaiveDateTime, tz: Tz, previous: Option<DateTime<FixedOffset>>) -> DateTime<FixedOffset> {
+    // Phones write wall clock time, so place it in the timezone. Around DST
+    // changes a wall clock time can happen twice or not at all.
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(t) => t.fixed_offset(),
+        // The clocks went back: take the earliest reading that doesn't go back in time
+        LocalResult::Ambiguous(early, late) => {
+            if previous.is_some_and(|p| early < p) { late.fixed_offset() } else { early.fixed_offset() }
+        }
+        // The clocks went forward past this time, read it with the offset from before the jump
+        LocalResult::None => {
+            let before = tz.offset_from_utc_datetime(&(naive - TimeDelta::days(1))).fix();
+            tz.from_utc_datetime(&(naive - before)).fixed_offset()
+        }
+    }
+}
+
+const DELETED: &[&str] = &["This message was deleted", "You deleted this message"];
+const EDITED: &str = "<This message was edited>";
+
+fn is_deleted(text: &str) -> bool {
+    // The whole text, so that a message starting with these words stays a message
+    let text = text.trim_start_matches('\u{200E}');
+    DELETED.contains(&text.strip_suffix('.').unwrap_or(text))
+}
+
+fn is_marked_message(text: &str, media: &MediaMatcher) -> bool {
+    // iOS also marks the attachments, deleted and edited messages of users
+    media.attachment(text).is_some() || is_deleted(text) || text.ends_with(EDITED)
+}
+
+fn split_header<'a>(
+    layout: Layout,
+    rest: &'a str,
+    chat_name: Option<&str>,
+    system: &SystemMatcher,
+    media: &MediaMatcher,
+) -> (MessageKind, &'a str, &'a str) {
+    // Splits the rest of a header line into the kind of message, its author and its text
+    match layout {
+        // Android writes system messages without an author: `3/1/17, 23:36 - Alice added Bob`
+        Layout::Android => {
+            if let Some((event, actor)) = system.event(rest) {
+                (MessageKind::System(event), actor, rest)
+            } else if let Some((author, text)) = rest.split_once(": ") {
+                (MessageKind::Text, author, text)
+            } else {
+                (MessageKind::System(SystemEvent::Other), "", rest)
+            }
+        }
+        // iOS attributes them to the chat, with the text behind a left-to-right mark
+        Layout::Ios => match rest.split_once(": ") {
+            Some((author, text)) => match text.strip_prefix('\u{200E}') {
+                Some(marked) => match system.event(marked) {
+                    Some((event, actor)) => (MessageKind::System(event), actor, marked),
+                    // An event no pattern knows, unless the chat is named after the user who sent this
+                    None if chat_name == Some(author) && !is_marked_message(marked, media) => {
+                        (MessageKind::System(SystemEvent::Other), "", marked)
+                    }
+                    None => (MessageKind::Text, author, text),
+                },
+                None => (MessageKind::Text, author, text),
+            },
+            None => (MessageKind::System(SystemEvent::Other), "", rest),
+        },
+    }
+}
+
+/// A message whose header has been read but whose continuation lines may still follow
+struct Pending {
+    timestamp: DateTime<FixedOffset>,
+    author: String,
+    buffer: String,
+    kind: MessageKind,
+}
+
+/// Parses messages one at a time from a chat export, so that only the message
+/// being read is held in memory
+pub struct MessageReader<R> {
+    reader: R,
+    options: ParseOptions,
+    source: Arc<str>, // the name of the input, kept on every message
+    matcher: LineMatcher,
+    system: SystemMatcher,
+    media: MediaMatcher,
+    buf: Vec<u8>,
+    line: usize, // number of the last line read
+    current: Option<Pending>,
+    previous_timestamp: Option<DateTime<FixedOffset>>,
+    chat_name: Option<String>, // iOS puts the name of the chat on system messages
+    report: ParseReport,
+    done: bool,
+}
+
+impl<R: BufRead> MessageReader<R> {
+    /// Read messages from `reader`, labelling them with `source`
+    pub fn new(reader: R, options: ParseOptions, source: &str) -> Self {
+        MessageReader {
+            reader,
+            options,
+            source: source.into(),
+            matcher: LineMatcher::new(),
+            system: SystemMatcher::new(),
+            media: MediaMatcher::new(),
+            buf: Vec::new(),
+            line: 0,
+            current: None,
+            previous_timestamp: None,
+            chat_name: None,
+            report: ParseReport::default(),
+            done: false,
+        }
+    }
+
+    /// The name of the input being read
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The format the input is read in
+    pub fn format(&self) -> Format {
+        self.options.format
+    }
+
+    /// The name of the chat, once an iOS system message has given it away
+    pub fn chat_name(&self) -> Option<&str> {
+        self.chat_name.as_deref()
+    }
+
+    /// The lines that couldn't be read cleanly so far
+    pub fn report(&self) -> &ParseReport {
+        &self.report
+    }
+
+    fn finish_message(&self, pending: Pending) -> Message {
+        // Builds a message from a header and its continuation lines, pulling out any
+        // attachment and the markers for deleted and edited messages
+        let Pending { timestamp, author, buffer, mut kind } = pending;
+        let mut text = buffer.trim();
+        let mut edited = false;
+        if kind == MessageKind::Text {
+            if let Some(rest) = text.strip_suffix(EDITED) {
+                text = rest.trim_end_matches([' ', '\u{200E}']);
+                edited = true;
+            }
+            if is_deleted(text) {
+                kind = MessageKind::Deleted;
+                text = "";
+            }
+        }
+
+        let (text, attachment) = match self.media.attachment(text) {
+            Some((mut attachment, caption)) if kind == MessageKind::Text => {
+                attachment.size = attachment.file.as_ref().and_then(|f| self.options.media_sizes.get(f)).copied();
+                (caption, Some(attachment))
+            }
+            _ => (text.to_string(), None),
+        };
+        let (author, text) = if self.options.normalize {
+            (normalize_owned(author), normalize_owned(text))
+        } else {
+            (author, text)
+        };
+        let source = self.source.clone();
+        Message { timestamp, author, text, kind, attachment, edited, source }
+    }
+
+    fn push_line(&mut self, line: &str) -> Result<Option<Message>, ParseError> {
+        // Feeds one line to the parser, returning the previous message once a new one starts
+        let format = self.options.format;
+        let error = |kind| ParseError { source: self.source.to_string(), line: self.line, text: line.to_string(), kind };
+        let header = match self.matcher.header(format.layout, line) {
+            Some(h) => match format.timestamp(&h) {
+                Some(naive) => Some((h, naive)),
+                None if self.options.strict => return Err(error(ParseErrorKind::InvalidTimestamp)),
+                None => {
+                    self.report.invalid_timestamps += 1;
+                    None
+                }
+            },
+            None => None,
+        };
+
+        if let Some((header, naive)) = header {
+            let mut timestamp = localize(naive, self.options.timezone, self.previous_timestamp);
+            self.previous_timestamp = Some(timestamp);
+            if let Some(display) = self.options.display_timezone {
+                timestamp = timestamp.with_timezone(&display).fixed_offset();
+            }
+            let (kind, author, text) = split_header(format.layout, header.rest, self.chat_name.as_deref(), &self.system, &self.media);
+            if let (Layout::Ios, MessageKind::System(_), None) = (format.layout, kind, &self.chat_name) {
+                self.chat_name = header.rest.split_once(": ").map(|(chat, _)| chat.to_string());
+            }
+            let pending = Pending { timestamp, author: author.to_string(), buffer: text.to_string(), kind };
+            return Ok(self.current.replace(pending).map(|p| self.finish_message(p)));
+        }
+
+        if let Some(current) = &mut self.current {
+            current.buffer.push('\n');
+            current.buffer.push_str(line);
+        } else if !line.trim().is_empty() {
+            if self.options.strict {
+                return Err(error(ParseErrorKind::NoMessage));
+            }
+            self.report.skipped += 1;
+        }
+        Ok(None)
+    }
+
+    fn next_message(&mut self) -> Result<Option<Message>, Error> {
+        loop {
+            let mut buf = std::mem::take(&mut self.buf);
+            let line = match read_line(&mut self.reader, &mut buf)? {
+                Some(line) => line,
+                None => return Ok(self.current.take().map(|p| self.finish_message(p))),
+            };
+            self.line += 1;
+            if let Cow::Owned(_) = line {
+                if self.options.strict {
+                    let source = self.source.to_string();
+                    let text = line.into_owned();
+                    return Err(ParseError { source, line: self.line, text, kind: ParseErrorKind::InvalidUtf8 }.into());
+                }
+                self.report.invalid_utf8 += 1;
+            }
+            let message = self.push_line(&line)?;
+            drop(line);
+            self.buf = buf;
+            if message.is_some() {
+                return Ok(message);
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for MessageReader<R> {
+    type Item = Result<Message, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let next = self.next_message();
+        if !matches!(next, Ok(Some(_))) {
+            self.done = true;
+        }
+        next.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{Clock, DateOrder};
+    use chrono::NaiveDate;
+
+    fn read(chat: &str, options: ParseOptions) -> Vec<Message> {
+        MessageReader::new(chat.as_bytes(), options, "chat.txt").collect::<Result<_, _>>().unwrap()
+    }
+
+    fn berlin() -> ParseOptions {
+        ParseOptions { timezone: chrono_tz::Europe::Berlin, ..ParseOptions::new(Format::default()) }
+    }
+
+    fn timestamps(messages: &[Message]) -> Vec<String> {
+        messages.iter().map(|m| m.timestamp.to_rfc3339()).collect()
+    }
+
+    #[test]
+    fn a_time_skipped_by_the_clocks_going_forward_keeps_the_offset_from_before() {
+        let naive = NaiveDate::from_ymd_opt(2021, 3, 28).unwrap().and_hms_opt(2, 30, 0).unwrap();
+        assert_eq!(localize(naive, chrono_tz::Europe::Berlin, None).to_rfc3339(), "2021-03-28T03:30:00+02:00");
+    }
+
+    #[test]
+    fn a_time_repeated_by_the_clocks_going_back_doesnt_go_back_in_time() {
+        let chat = "31/10/2021, 02:30 - Sam: before\n31/10/2021, 02:10 - Sam: after\n";
+        let messages = read(chat, berlin());
+        assert_eq!(timestamps(&messages), ["2021-10-31T02:30:00+02:00", "2021-10-31T02:10:00+01:00"]);
+
+        let options = ParseOptions { display_timezone: Some(Tz::UTC), ..berlin() };
+        let messages = read(chat, options);
+        assert_eq!(timestamps(&messages), ["2021-10-31T00:30:00+00:00", "2021-10-31T01:10:00+00:00"]);
+    }
+
+    fn strict() -> ParseOptions {
+        ParseOptions { strict: true, ..ParseOptions::new(Format::default()) }
+    }
+
+    fn first_error(chat: &[u8]) -> ParseError {
+        match MessageReader::new(chat, strict(), "chat.txt").find_map(Result::err) {
+            Some(Error::Parse(e)) => e,
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    fn lenient_report(chat: &[u8]) -> ParseReport {
+        let mut reader = MessageReader::new(chat, ParseOptions::new(Format::default()), "chat.txt");
+        for message in reader.by_ref() {
+            message.unwrap();
+        }
+        std::mem::take(&mut reader.report)
+    }
+
+    #[test]
+    fn text_before_the_first_message() {
+        let chat = b"exported by Sam\n01/03/2021, 10:00 - Sam: hi\n";
+        let error = first_error(chat);
+        assert_eq!((error.kind, error.line, error.text.as_str()), (ParseErrorKind::NoMessage, 1, "exported by Sam"));
+        let report = lenient_report(chat);
+        assert_eq!((report.skipped, report.invalid_timestamps, report.invalid_utf8), (1, 0, 0));
+    }
+
+    #[test]
+    fn an_impossible_timestamp() {
+        let chat = b"01/03/2021, 10:00 - Sam: hi\n31/02/2021, 10:00 - Sam: when?\n";
+        let error = first_error(chat);
+        assert_eq!((error.kind, error.line), (ParseErrorKind::InvalidTimestamp, 2));
+        let report = lenient_report(chat);
+        assert_eq!((report.skipped, report.invalid_timestamps, report.invalid_utf8), (0, 1, 0));
+        // Leniently, the line is kept as part of the message before it
+        let messages = read(std::str::from_utf8(chat).unwrap(), ParseOptions::new(Format::default()));
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].text, "hi\n31/02/2021, 10:00 - Sam: when?");
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let chat = b"01/03/2021, 10:00 - Sam: hi\n01/03/2021, 10:01 - Sam: caf\xe9\n";
+        let error = first_error(chat);
+        assert_eq!((error.kind, error.line), (ParseErrorKind::InvalidUtf8, 2));
+        let report = lenient_report(chat);
+        assert_eq!((report.skipped, report.invalid_timestamps, report.invalid_utf8), (0, 0, 1));
+    }
+
+    #[test]
+    fn unknown_ios_events_come_from_the_chat() {
+        let ios = Format { layout: Layout::Ios, date_order: DateOrder::MonthFirst, clock: Clock::H12, ..Format::default() };
+        let chat = "[3/14/17, 1:00:00 AM] Friends: \u{200E}Messages and calls are end-to-end encrypted.\n\
+                    [3/14/17, 1:03:00 AM] Friends: \u{200E}Sam pinned a message\n\
+                    [3/14/17, 1:04:00 AM] Sam: hi\n";
+        let messages = read(chat, ParseOptions::new(ios));
+        let kinds: Vec<_> = messages.iter().map(|m| (m.kind, m.author.as_str())).collect();
+        assert_eq!(
+            kinds,
+            [
+                (MessageKind::System(SystemEvent::Encryption), ""),
+                (MessageKind::System(SystemEvent::Other), ""),
+                (MessageKind::Text, "Sam"),
+            ]
+        );
+        assert_eq!(messages[1].text, "Sam pinned a message");
+
+        // In a chat named after a user, their attachments and deleted messages are still theirs
+        let chat = "[3/14/17, 1:00:00 AM] Sam: \u{200E}Messages and calls are end-to-end encrypted.\n\
+                    [3/14/17, 1:05:00 AM] Sam: \u{200E}<attached: 00000012-PHOTO-2017-03-14-01-05-00.jpg>\n\
+                    [3/14/17, 1:06:00 AM] Sam: \u{200E}This message was deleted.\n";
+        let kinds: Vec<_> = read(chat, ParseOptions::new(ios)).iter().map(|m| (m.kind, m.author.clone())).collect();
+        assert_eq!(kinds[1], (MessageKind::Text, "Sam".to_string()));
+        assert_eq!(kinds[2], (MessageKind::Deleted, "Sam".to_string()));
+    }
+
+    #[test]
+    fn only_the_whole_marker_makes_a_message_deleted() {
+        let chat = "01/03/2021, 10:00 - Sam: This message was deleted\n\
+                    01/03/2021, 10:01 - Sam: \u{200E}You deleted this message.\n\
+                    01/03/2021, 10:02 - Sam: This message was deleted by mistake?\n";
+        let kinds: Vec<_> = read(chat, ParseOptions::new(Format::default())).iter().map(|m| m.kind).collect();
+        assert_eq!(kinds, [MessageKind::Deleted, MessageKind::Deleted, MessageKind::Text]);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::error::{Error, ParseReport};
+use crate::format::Format;
+use crate::message::Message;
+use crate::parse::MessageReader;
+use std::io::BufRead;
+
+/// A chat export from some messenger, read as a stream of messages.
+/// Stats, merging and listing authors work the same on any source.
+pub trait ChatSource: Iterator<Item = Result<Message, Error>> {
+    /// The name messages from this source are labelled with
+    fn source(&self) -> &str;
+
+    /// What couldn't be read cleanly so far
+    fn report(&self) -> &ParseReport;
+
+    /// The WhatsApp format the chat is written in, if it is a WhatsApp export
+    fn format(&self) -> Option<Format> {
+        None
+    }
+
+    /// The name of the chat, when the export gives it
+    fn chat_name(&self) -> Option<&str> {
+        None
+    }
+}
+
+impl<S: ChatSource + ?Sized> ChatSource for Box<S> {
+    fn source(&self) -> &str {
+        (**self).source()
+    }
+
+    fn report(&self) -> &ParseReport {
+        (**self).report()
+    }
+
+    fn format(&self) -> Option<Format> {
+        (**self).format()
+    }
+
+    fn chat_name(&self) -> Option<&str> {
+        (**self).chat_name()
+    }
+}
+
+impl<R: BufRead> ChatSource for MessageReader<R> {
+    fn source(&self) -> &str {
+        MessageReader::source(self)
+    }
+
+    fn report(&self) -> &ParseReport {
+        MessageReader::report(self)
+    }
+
+    fn format(&self) -> Option<Format> {
+        Some(MessageReader::format(self))
+    }
+
+    fn chat_name(&self) -> Option<&str> {
+        MessageReader::chat_name(self)
+    }
+}
+
+/// The messenger an export comes from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "cli", derive(clap::ValueEnum))]
+pub enum SourceKind {
+    /// Tell from the file: WhatsApp text and zips, a JSON export, an SMS backup or a CSV/TSV table
+    Auto,
+    /// WhatsApp "Export chat" text or zip
+    Whatsapp,
+    /// Telegram Desktop's JSON export (result.json)
+    Telegram,
+    /// A Signal backup decrypted to JSON
+    Signal,
+    /// Facebook Messenger or Instagram JSON from "Download your information"
+    Messenger,
+    /// SMS and MMS backed up to XML by the Android app SMS Backup & Restore
+    Sms,
+    /// A CSV or TSV table with a row per message
+    Csv,
+}
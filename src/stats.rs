@@ -0,0 +1,289 @@
+use crate::media::AttachmentKind;
+use crate::message::{Message, MessageKind};
+use crate::system::SystemEvent;
+use chrono::{DateTime, Datelike, FixedOffset, Timelike};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// How many messages were sent in each hour of each day of the week, in the
+/// timezone the messages are shown in. Rows are days from Monday, columns hours
+/// from midnight.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct Heatmap([[u64; 24]; 7]);
+
+impl Heatmap {
+    /// Count a message sent at `timestamp`
+    pub fn add(&mut self, timestamp: DateTime<FixedOffset>) {
+        self.0[timestamp.weekday().num_days_from_monday() as usize][timestamp.hour() as usize] += 1;
+    }
+
+    /// Fold in the counts of another heatmap
+    pub fn merge(&mut self, other: &Heatmap) {
+        for (row, other) in self.0.iter_mut().zip(&other.0) {
+            for (count, other) in row.iter_mut().zip(other) {
+                *count += other;
+            }
+        }
+    }
+
+    /// The counts per hour of each day, Monday first
+    pub fn rows(&self) -> &[[u64; 24]; 7] {
+        &self.0
+    }
+
+    /// The count of the busiest hour
+    pub fn max(&self) -> u64 {
+        self.0.iter().flatten().copied().max().unwrap_or(0)
+    }
+}
+
+/// The stats of one user
+#[derive(Debug, Serialize)]
+pub struct Stat {
+    /// The user
+    pub user: String,
+    /// The number of messages the user sent
+    pub num_messages: u64,
+    /// The number of words the user sent
+    pub num_words: u64,
+    /// The date of the first message the user sent
+    pub first_message: DateTime<FixedOffset>,
+    /// The number of attachments the user sent of each kind
+    pub media: BTreeMap<AttachmentKind, u64>,
+    /// The number of messages the user deleted
+    pub num_deleted: u64,
+    /// The number of messages the user edited
+    pub num_edited: u64,
+    /// The size of the attachments the user sent, when known
+    pub media_bytes: u64,
+    /// The percentage of all messages that the user sent
+    pub percent_messages: f32,
+    /// The percentage of all words that the user sent
+    pub percent_words: f32,
+    /// When in the week the user sends messages
+    pub activity: Heatmap,
+}
+
+/// Accumulates stats one message at a time, so memory only grows with the number of users
+#[derive(Debug, Default)]
+pub struct StatsBuilder {
+    users: HashMap<String, Stat>,
+    total_messages: u64,
+    total_words: u64,
+    activity: Heatmap,
+    system: HashMap<SystemEvent, u64>,
+}
+
+impl StatsBuilder {
+    /// Count one message
+    pub fn add(&mut self, m: &Message) {
+        // For each message, count:
+        // 1. the number of messages each user sent
+        // 2. the number of words each user sent, not counting media placeholders
+        // 3. the number of attachments of each kind each user sent
+        // 4. the number of messages each user deleted or edited
+        // 5. the number of bytes of media each user sent, when the export has the files
+        // 6. the hour of the week each message was sent in, for each user and the whole chat
+        // Picking the messages to count, e.g. of some dates or users, is left to a Filter beforehand
+        // System events aren't written by anyone, so they are only counted by kind
+        if let MessageKind::System(event) = m.kind {
+            *self.system.entry(event).or_default() += 1;
+            return;
+        }
+
+        let words = m.text.split_whitespace().count() as u64;
+        self.total_messages += 1;
+        self.total_words += words;
+        self.activity.add(m.timestamp);
+
+        let entry = self.users.entry(m.author.clone()).or_insert_with(|| Stat {
+            user: m.author.clone(),
+            num_messages: 0,
+            num_words: 0,
+            first_message: m.timestamp,
+            media: BTreeMap::new(),
+            num_deleted: 0,
+            num_edited: 0,
+            media_bytes: 0,
+            percent_messages: 0.0,
+            percent_words: 0.0,
+            activity: Heatmap::default(),
+        });
+        entry.num_messages += 1;
+        entry.num_words += words;
+        if let Some(attachment) = &m.attachment {
+            *entry.media.entry(attachment.kind).or_default() += 1;
+            entry.media_bytes += attachment.size.unwrap_or(0);
+        }
+        if m.kind == MessageKind::Deleted {
+            entry.num_deleted += 1;
+        }
+        if m.edited {
+            entry.num_edited += 1;
+        }
+        entry.activity.add(m.timestamp);
+        if m.timestamp < entry.first_message {
+            entry.first_message = m.timestamp;
+        }
+    }
+
+    /// Fold in the stats of another builder, as if its messages had been added to this one
+    pub fn merge(&mut self, other: StatsBuilder) {
+        self.total_messages += other.total_messages;
+        self.total_words += other.total_words;
+        self.activity.merge(&other.activity);
+        for (event, count) in other.system {
+            *self.system.entry(event).or_default() += count;
+        }
+        for (user, stat) in other.users {
+            self.merge_user(user, stat);
+        }
+    }
+
+    fn merge_user(&mut self, user: String, stat: Stat) {
+        match self.users.get_mut(&user) {
+            None => {
+                self.users.insert(user, stat);
+            }
+            Some(entry) => {
+                entry.num_messages += stat.num_messages;
+                entry.num_words += stat.num_words;
+                for (kind, count) in stat.media {
+                    *entry.media.entry(kind).or_default() += count;
+                }
+                entry.num_deleted += stat.num_deleted;
+                entry.num_edited += stat.num_edited;
+                entry.media_bytes += stat.media_bytes;
+                entry.first_message = entry.first_message.min(stat.first_message);
+                entry.activity.merge(&stat.activity);
+            }
+        }
+    }
+
+    /// Count everything `from` did as done by `to`, merging their rows
+    pub fn rename(&mut self, from: &str, to: &str) {
+        if let Some(mut stat) = self.users.remove(from) {
+            stat.user = to.to_string();
+            self.merge_user(to.to_string(), stat);
+        }
+    }
+
+    /// When in the week messages are sent in the whole chat
+    pub fn activity(&self) -> &Heatmap {
+        &self.activity
+    }
+
+    /// How often each kind of system event happened, most frequent first
+    pub fn system_stats(&self) -> Vec<(SystemEvent, u64)> {
+        let mut counts: Vec<(SystemEvent, u64)> = self.system.iter().map(|(&e, &c)| (e, c)).collect();
+        counts.sort_by_key(|&(event, count)| (std::cmp::Reverse(count), event));
+        counts
+    }
+
+    /// The per-user stats, with percentages of the totals filled in
+    pub fn into_stats(self) -> Vec<Stat> {
+        let total_messages = self.total_messages as f32;
+        let total_words = self.total_words as f32;
+        let mut stats: Vec<Stat> = self.users.into_values().collect();
+        for stat in &mut stats {
+            stat.percent_messages = stat.num_messages as f32 / total_messages * 100.0;
+            stat.percent_words = stat.num_words as f32 / total_words * 100.0;
+        }
+        stats
+    }
+}
+
+/// Build stats for `messages` in parallel, split into groups by `key`
+pub fn aggregate<K, F>(messages: &[Message], key: F) -> BTreeMap<K, StatsBuilder>
+where
+    K: Ord + Send,
+    F: Fn(&Message) -> K + Sync,
+{
+    messages
+        .par_iter()
+        .fold(BTreeMap::new, |mut groups: BTreeMap<K, StatsBuilder>, m| {
+            groups.entry(key(m)).or_default().add(m);
+            groups
+        })
+        .reduce(BTreeMap::new, |mut a, b| {
+            merge_groups(&mut a, b);
+            a
+        })
+}
+
+/// Merge grouped stats from `from` into `into`
+pub fn merge_groups<K: Ord>(into: &mut BTreeMap<K, StatsBuilder>, from: BTreeMap<K, StatsBuilder>) {
+    for (key, builder) in from {
+        into.entry(key).or_default().merge(builder);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::Attachment;
+    use chrono::TimeDelta;
+
+    fn messages() -> Vec<Message> {
+        let start = DateTime::parse_from_rfc3339("2021-03-01T00:00:00+01:00").unwrap();
+        let authors = ["Sam", "Bob", "Alice"];
+        (0..10_000)
+            .map(|i| Message {
+                timestamp: start + TimeDelta::minutes(i * 37),
+                author: authors[i as usize % 3].to_string(),
+                text: "word ".repeat(i as usize % 5),
+                kind: match i % 50 {
+                    0 => MessageKind::Deleted,
+                    1 => MessageKind::System(SystemEvent::Added),
+                    _ => MessageKind::Text,
+                },
+                attachment: (i % 7 == 0).then_some(Attachment { kind: AttachmentKind::Image, file: None, size: Some(100) }),
+                edited: i % 11 == 0,
+                source: "chat.txt".into(),
+            })
+            .collect()
+    }
+
+    fn assert_same(parallel: StatsBuilder, sequential: StatsBuilder) {
+        assert_eq!(parallel.activity(), sequential.activity());
+        assert_eq!(parallel.system_stats(), sequential.system_stats());
+        let mut parallel = parallel.into_stats();
+        let mut sequential = sequential.into_stats();
+        parallel.sort_by(|a, b| a.user.cmp(&b.user));
+        sequential.sort_by(|a, b| a.user.cmp(&b.user));
+        assert_eq!(parallel.len(), sequential.len());
+        for (p, s) in parallel.iter().zip(&sequential) {
+            assert_eq!(
+                (&p.user, p.num_messages, p.num_words, p.first_message, &p.media),
+                (&s.user, s.num_messages, s.num_words, s.first_message, &s.media)
+            );
+            assert_eq!((p.num_deleted, p.num_edited, p.media_bytes), (s.num_deleted, s.num_edited, s.media_bytes));
+            assert_eq!((p.percent_messages, p.percent_words), (s.percent_messages, s.percent_words));
+            assert_eq!(p.activity, s.activity);
+        }
+    }
+
+    #[test]
+    fn parallel_chunks_add_up_to_the_sequential_stats() {
+        let messages = messages();
+        let key = |m: &Message| m.timestamp.hour() < 12;
+        for threads in [1, 4] {
+            let mut sequential: BTreeMap<bool, StatsBuilder> = BTreeMap::new();
+            for m in &messages {
+                sequential.entry(key(m)).or_default().add(m);
+            }
+            // In chunks, as the tool hands them over
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+            let mut parallel = BTreeMap::new();
+            for chunk in messages.chunks(3_000) {
+                merge_groups(&mut parallel, pool.install(|| aggregate(chunk, key)));
+            }
+            assert_eq!(parallel.keys().collect::<Vec<_>>(), sequential.keys().collect::<Vec<_>>());
+            for (parallel, sequential) in parallel.into_values().zip(sequential.into_values()) {
+                assert_same(parallel, sequential);
+            }
+        }
+    }
+}
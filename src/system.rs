@@ -0,0 +1,113 @@
+use regex::Regex;
+use std::fmt;
+
+/// Events WhatsApp writes into the chat itself rather than a user
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SystemEvent {
+    /// The notice that messages are end-to-end encrypted
+    Encryption,
+    /// The group was created
+    Created,
+    /// Someone was added to the group
+    Added,
+    /// Someone was removed from the group
+    Removed,
+    /// Someone left the group
+    Left,
+    /// Someone joined with an invite link
+    Joined,
+    /// The group's subject was changed
+    SubjectChanged,
+    /// The group's description was changed
+    DescriptionChanged,
+    /// The group's icon was changed or deleted
+    IconChanged,
+    /// Who may send messages or edit the group was changed
+    SettingsChanged,
+    /// Someone became or stopped being an admin
+    AdminChanged,
+    /// Disappearing messages were turned on, off or changed
+    DisappearingMessages,
+    /// Someone changed their phone number
+    NumberChanged,
+    /// Someone's security code changed
+    SecurityCodeChanged,
+    /// Someone reacted to a message, in exports that list reactions
+    Reaction,
+    /// Any other event
+    Other,
+}
+
+impl fmt::Display for SystemEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            SystemEvent::Encryption => "encryption notice",
+            SystemEvent::Created => "group created",
+            SystemEvent::Added => "member added",
+            SystemEvent::Removed => "member removed",
+            SystemEvent::Left => "member left",
+            SystemEvent::Joined => "member joined via link",
+            SystemEvent::SubjectChanged => "subject changed",
+            SystemEvent::DescriptionChanged => "description changed",
+            SystemEvent::IconChanged => "icon changed",
+            SystemEvent::SettingsChanged => "settings changed",
+            SystemEvent::AdminChanged => "admin changed",
+            SystemEvent::DisappearingMessages => "disappearing messages",
+            SystemEvent::NumberChanged => "number changed",
+            SystemEvent::SecurityCodeChanged => "security code changed",
+            SystemEvent::Reaction => "reaction",
+            SystemEvent::Other => "other",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// The actor of an event never contains a colon, which keeps `Name: I added sugar` a user message
+const PATTERNS: &[(SystemEvent, &str)] = &[
+    (SystemEvent::Encryption, r"^Messages (?:and calls are|to this group are now secured with) end-to-end encrypt"),
+    (SystemEvent::Created, r#"^(?P<actor>[^:]+?) created (?:group ".*"|this group)"#),
+    (SystemEvent::Added, r"^(?P<actor>[^:]+?) added .+"),
+    (SystemEvent::Removed, r"^(?P<actor>[^:]+?) removed .+"),
+    (SystemEvent::Left, r"^(?P<actor>[^:]+?) left$"),
+    (SystemEvent::Joined, r"^(?P<actor>[^:]+?) joined using (?:this group's invite link|a group link|an invite link)"),
+    (SystemEvent::SubjectChanged, r#"^(?P<actor>[^:]+?) changed (?:the subject|the group name|group name) (?:from ".*" )?to ".*""#),
+    (SystemEvent::DescriptionChanged, r"^(?P<actor>[^:]+?) (?:changed|deleted) the group description"),
+    (SystemEvent::IconChanged, r"^(?P<actor>[^:]+?) (?:changed|deleted|removed) (?:this group's|the group) icon"),
+    (SystemEvent::SettingsChanged, r"^(?P<actor>[^:]+?) changed (?:this group's settings|the group settings|the settings)"),
+    (SystemEvent::AdminChanged, r"^(?P<actor>[^:]+?) (?:is|are|'re) now an admin"),
+    (SystemEvent::DisappearingMessages, r"^(?P<actor>[^:]+?) turned (?:on|off) disappearing messages"),
+    (SystemEvent::NumberChanged, r"^(?P<actor>[^:]+?) changed (?:their phone number|to \+?[\d ()-]+$)"),
+    (SystemEvent::SecurityCodeChanged, r"^Your security code with (?P<actor>[^:]+?) changed"),
+    (SystemEvent::SecurityCodeChanged, r"^(?P<actor>[^:]+?)'s security code changed"),
+];
+
+pub(crate) struct SystemMatcher {
+    patterns: Vec<(SystemEvent, Regex)>,
+}
+
+impl Default for SystemMatcher {
+    fn default() -> Self {
+        SystemMatcher::new()
+    }
+}
+
+impl SystemMatcher {
+    pub fn new() -> Self {
+        let patterns = PATTERNS
+            .iter()
+            .map(|(event, pattern)| (*event, Regex::new(pattern).unwrap()))
+            .collect();
+        SystemMatcher { patterns }
+    }
+
+    /// The event a line of text describes and who caused it ("" when nobody did),
+    /// or None if it doesn't look like a system message
+    pub fn event<'a>(&self, text: &'a str) -> Option<(SystemEvent, &'a str)> {
+        self.patterns.iter().find_map(|(event, re)| {
+            re.captures(text).map(|caps| {
+                let actor = caps.name("actor").map_or("", |m| m.as_str());
+                (*event, actor)
+            })
+        })
+    }
+}